@@ -0,0 +1,13 @@
+CREATE SEQUENCE IF NOT EXISTS questions_account_id_seq OWNED BY questions.account_id;
+CREATE SEQUENCE IF NOT EXISTS answers_account_id_seq OWNED BY answers.account_id;
+
+UPDATE questions SET account_id = nextval('questions_account_id_seq') WHERE account_id IS NULL;
+UPDATE answers SET account_id = nextval('answers_account_id_seq') WHERE account_id IS NULL;
+
+ALTER TABLE questions
+ALTER COLUMN account_id SET DEFAULT nextval('questions_account_id_seq'),
+ALTER COLUMN account_id SET NOT NULL;
+
+ALTER TABLE answers
+ALTER COLUMN account_id SET DEFAULT nextval('answers_account_id_seq'),
+ALTER COLUMN account_id SET NOT NULL;
@@ -0,0 +1,14 @@
+ALTER TABLE questions
+ALTER COLUMN account_id DROP DEFAULT,
+ALTER COLUMN account_id DROP NOT NULL;
+
+ALTER TABLE answers
+ALTER COLUMN account_id DROP DEFAULT,
+ALTER COLUMN account_id DROP NOT NULL;
+
+-- The serial default filled these with sequence values rather than real owners
+UPDATE questions SET account_id = NULL;
+UPDATE answers SET account_id = NULL;
+
+DROP SEQUENCE IF EXISTS questions_account_id_seq;
+DROP SEQUENCE IF EXISTS answers_account_id_seq;
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS answers_one_accepted_per_question;
+
+ALTER TABLE answers
+DROP COLUMN accepted;
+
+DROP TABLE IF EXISTS answer_votes;
+DROP TABLE IF EXISTS question_votes;
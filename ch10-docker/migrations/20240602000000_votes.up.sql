@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS question_votes (
+ question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+ account_id integer NOT NULL,
+ value smallint NOT NULL CHECK (value IN (-1, 1)),
+ created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+ PRIMARY KEY (question_id, account_id)
+);
+
+CREATE TABLE IF NOT EXISTS answer_votes (
+ answer_id integer NOT NULL REFERENCES answers ON DELETE CASCADE,
+ account_id integer NOT NULL,
+ value smallint NOT NULL CHECK (value IN (-1, 1)),
+ created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+ PRIMARY KEY (answer_id, account_id)
+);
+
+ALTER TABLE answers
+ADD COLUMN accepted BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- A question can have at most one accepted answer
+CREATE UNIQUE INDEX IF NOT EXISTS answers_one_accepted_per_question
+ ON answers (corresponding_question) WHERE accepted;
@@ -0,0 +1,2 @@
+-- The tag clean up itself cannot be undone
+DROP INDEX IF EXISTS questions_tags_idx;
@@ -0,0 +1,13 @@
+-- Lower case, collapse whitespace and drop duplicate or empty tags, keeping their order
+UPDATE questions SET tags = (
+ SELECT array_agg(cleaned.tag ORDER BY cleaned.position)
+ FROM (
+  SELECT lower(regexp_replace(btrim(raw.tag), '\s+', ' ', 'g')) AS tag, MIN(raw.position) AS position
+  FROM unnest(questions.tags) WITH ORDINALITY AS raw(tag, position)
+  WHERE btrim(raw.tag) <> ''
+  GROUP BY 1
+ ) cleaned
+)
+WHERE tags IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS questions_tags_idx ON questions USING GIN (tags);
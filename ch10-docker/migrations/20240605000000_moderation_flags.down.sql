@@ -0,0 +1,2 @@
+ALTER TABLE answers DROP COLUMN flagged;
+ALTER TABLE questions DROP COLUMN flagged;
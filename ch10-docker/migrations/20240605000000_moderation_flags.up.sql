@@ -0,0 +1,3 @@
+-- Set when the moderator finds offending words and the mode is flag
+ALTER TABLE questions ADD COLUMN flagged BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE answers ADD COLUMN flagged BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS reports;
+ALTER TABLE answers DROP COLUMN hidden;
+ALTER TABLE questions DROP COLUMN hidden;
@@ -0,0 +1,23 @@
+-- Hidden posts are left out of every public listing until a moderator approves them
+ALTER TABLE questions ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE answers ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT false;
+
+-- A report points at either a question or an answer
+CREATE TABLE IF NOT EXISTS reports (
+ id serial PRIMARY KEY,
+ question_id integer REFERENCES questions ON DELETE CASCADE,
+ answer_id integer REFERENCES answers ON DELETE CASCADE,
+ account_id integer NOT NULL,
+ reason TEXT NOT NULL,
+ created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+ resolved_on TIMESTAMP,
+ resolved_by integer,
+ resolution TEXT CHECK (resolution IN ('approved', 'hidden')),
+ CHECK ((question_id IS NULL) <> (answer_id IS NULL))
+);
+
+-- One open report per account and post
+CREATE UNIQUE INDEX IF NOT EXISTS reports_open_question
+ ON reports (question_id, account_id) WHERE resolved_on IS NULL AND question_id IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS reports_open_answer
+ ON reports (answer_id, account_id) WHERE resolved_on IS NULL AND answer_id IS NOT NULL;
@@ -0,0 +1,4 @@
+-- user, moderator or admin, every existing account starts out as a user
+ALTER TABLE accounts
+ADD COLUMN role VARCHAR(16) NOT NULL DEFAULT 'user'
+ CHECK (role IN ('user', 'moderator', 'admin'));
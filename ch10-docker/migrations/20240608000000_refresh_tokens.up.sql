@@ -0,0 +1,19 @@
+-- Every login starts a family, refreshing rotates tokens within it
+-- Revoking a family logs that login out, access tokens carry the family id
+CREATE TABLE IF NOT EXISTS token_families (
+ id serial PRIMARY KEY,
+ account_id integer NOT NULL,
+ created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+ revoked_on TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS token_families_account_idx ON token_families (account_id);
+
+-- Only the SHA-256 hash of a refresh token is stored
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+ token_hash VARCHAR(64) PRIMARY KEY,
+ family_id integer NOT NULL REFERENCES token_families ON DELETE CASCADE,
+ created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+ expires_on TIMESTAMP NOT NULL,
+ used_on TIMESTAMP
+);
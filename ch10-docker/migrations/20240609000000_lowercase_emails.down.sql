@@ -0,0 +1,2 @@
+-- The original spelling of the emails is not kept, nothing to undo
+SELECT 1;
@@ -0,0 +1,23 @@
+-- Emails are trimmed and compared lower cased from now on
+-- Accounts whose emails only differ in case or surrounding spaces cannot all keep their address,
+-- and logins could only ever reach one of them, so the migration stops and lists them instead.
+-- Merge or delete all but one account of each group by hand, then start the server again.
+DO $$
+DECLARE
+    clashes TEXT;
+BEGIN
+    SELECT string_agg(emails, '; ') INTO clashes
+    FROM (
+        SELECT string_agg(email, ', ' ORDER BY id) AS emails
+        FROM accounts
+        GROUP BY LOWER(TRIM(email))
+        HAVING COUNT(*) > 1
+    ) AS groups;
+
+    IF clashes IS NOT NULL THEN
+        RAISE EXCEPTION 'accounts only differ in the case of their email: %', clashes
+            USING HINT = 'Keep one account per email, delete or rename the others, then migrate again';
+    END IF;
+END $$;
+
+UPDATE accounts SET email = LOWER(TRIM(email)) WHERE email <> LOWER(TRIM(email));
@@ -0,0 +1,10 @@
+-- Only the SHA-256 hash of a reset token is stored, each token works once
+CREATE TABLE IF NOT EXISTS password_resets (
+ token_hash VARCHAR(64) PRIMARY KEY,
+ account_id integer NOT NULL,
+ created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+ expires_on TIMESTAMP NOT NULL,
+ used_on TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS password_resets_account_idx ON password_resets (account_id);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS answers_question_created_on_idx;
+DROP INDEX IF EXISTS questions_created_on_idx;
+
+ALTER TABLE questions
+DROP COLUMN IF EXISTS created_on;
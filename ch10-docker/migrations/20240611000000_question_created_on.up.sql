@@ -0,0 +1,8 @@
+-- Existing questions all get the time of the migration, their ids keep them in order
+ALTER TABLE questions
+ADD COLUMN created_on TIMESTAMP NOT NULL DEFAULT NOW();
+
+-- Keyset paging walks both listings in (created_on, id) order
+CREATE INDEX IF NOT EXISTS questions_created_on_idx ON questions (created_on, id);
+CREATE INDEX IF NOT EXISTS answers_question_created_on_idx
+    ON answers (corresponding_question, created_on, id);
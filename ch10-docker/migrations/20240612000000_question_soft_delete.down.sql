@@ -0,0 +1,9 @@
+-- Soft deleted questions would show up again, remove them for good
+DELETE FROM questions WHERE deleted_at IS NOT NULL;
+ALTER TABLE questions
+DROP COLUMN IF EXISTS deleted_at;
+
+ALTER TABLE answers DROP CONSTRAINT IF EXISTS answers_corresponding_question_fkey;
+ALTER TABLE answers
+ADD CONSTRAINT answers_corresponding_question_fkey
+    FOREIGN KEY (corresponding_question) REFERENCES questions;
@@ -0,0 +1,9 @@
+-- Deleting a question takes its answers with it in the same statement
+ALTER TABLE answers DROP CONSTRAINT IF EXISTS answers_corresponding_question_fkey;
+ALTER TABLE answers
+ADD CONSTRAINT answers_corresponding_question_fkey
+    FOREIGN KEY (corresponding_question) REFERENCES questions ON DELETE CASCADE;
+
+-- Set when a question is soft deleted, it is left out everywhere until restored
+ALTER TABLE questions
+ADD COLUMN deleted_at TIMESTAMP;
@@ -0,0 +1,21 @@
+-- Every edit of a question or answer is kept as a numbered revision
+-- Revision 1 is the text as first posted, it is written on the first edit
+CREATE TABLE IF NOT EXISTS question_revisions (
+ question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+ revision integer NOT NULL,
+ title VARCHAR (255) NOT NULL,
+ content TEXT NOT NULL,
+ tags TEXT [],
+ account_id integer,
+ created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+ PRIMARY KEY (question_id, revision)
+);
+
+CREATE TABLE IF NOT EXISTS answer_revisions (
+ answer_id integer NOT NULL REFERENCES answers ON DELETE CASCADE,
+ revision integer NOT NULL,
+ content TEXT NOT NULL,
+ account_id integer,
+ created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+ PRIMARY KEY (answer_id, revision)
+);
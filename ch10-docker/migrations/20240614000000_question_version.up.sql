@@ -0,0 +1,3 @@
+-- Counts the edits of a question, clients send it back in If-Match so concurrent edits are caught
+ALTER TABLE questions
+ADD COLUMN version integer NOT NULL DEFAULT 1;
@@ -0,0 +1,957 @@
+use clap::{ArgAction, Parser, Subcommand};
+use serde::Deserialize;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::moderation::ModerationMode;
+use crate::types::account::Role;
+
+// Command line flags, each one falls back to an environment variable
+// Anything left unset here is taken from the config file, then the defaults
+#[derive(Parser, Debug)]
+#[command(about = "Questions and answers REST API")]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Optional TOML file with the same settings as the flags below
+    #[arg(long = "config", env = "CONFIG_FILE")]
+    pub config_file: Option<PathBuf>,
+
+    #[arg(long, env = "PG_HOST")]
+    pub db_host: Option<String>,
+    #[arg(long, env = "PG_PORT")]
+    pub db_port: Option<u16>,
+    #[arg(long, env = "PG_DBNAME")]
+    pub db_name: Option<String>,
+    #[arg(long, env = "PG_USER")]
+    pub db_user: Option<String>,
+    #[arg(long, env = "PG_PASSWORD", hide_env_values = true)]
+    pub db_password: Option<String>,
+    /// File holding the database password, as used by Docker secrets
+    #[arg(long, env = "PG_PASSWORDFILE")]
+    pub db_password_file: Option<PathBuf>,
+    #[arg(long, env = "PG_MAX_CONNECTIONS")]
+    pub db_max_connections: Option<u32>,
+
+    /// Address the server listens on, e.g. 0.0.0.0:8000
+    #[arg(long, env = "BIND_ADDRESS")]
+    pub bind_address: Option<String>,
+    /// Comma separated list of origins allowed by CORS
+    #[arg(long, env = "ALLOWED_ORIGINS", value_delimiter = ',')]
+    pub allowed_origins: Option<Vec<String>>,
+
+    /// Key used to encrypt PASETO tokens, must be exactly 32 bytes
+    #[arg(long, env = "TOKEN_SECRET", hide_env_values = true)]
+    pub token_secret: Option<String>,
+    /// File holding the token key, as used by Docker secrets
+    #[arg(long, env = "TOKEN_SECRET_FILE")]
+    pub token_secret_file: Option<PathBuf>,
+    /// How long an access token stays valid
+    #[arg(long, env = "TOKEN_LIFETIME_MINUTES")]
+    pub token_lifetime_minutes: Option<i64>,
+    /// How long a refresh token stays valid if it is not used
+    #[arg(long, env = "REFRESH_TOKEN_LIFETIME_DAYS")]
+    pub refresh_token_lifetime_days: Option<i64>,
+
+    /// Load the built in questions at startup when the questions table is empty
+    #[arg(long, env = "SEED_ON_STARTUP", action = ArgAction::Set)]
+    pub seed_on_startup: Option<bool>,
+
+    /// Keep deleted questions in the database so they can be restored
+    #[arg(long, env = "SOFT_DELETE", action = ArgAction::Set)]
+    pub soft_delete: Option<bool>,
+
+    /// Shortest password accepted on registration and password changes
+    #[arg(long, env = "PASSWORD_MIN_LENGTH")]
+    pub password_min_length: Option<usize>,
+    /// Require both lower and upper case letters in passwords
+    #[arg(long, env = "PASSWORD_REQUIRE_MIXED_CASE", action = ArgAction::Set)]
+    pub password_require_mixed_case: Option<bool>,
+    /// Require at least one digit in passwords
+    #[arg(long, env = "PASSWORD_REQUIRE_DIGIT", action = ArgAction::Set)]
+    pub password_require_digit: Option<bool>,
+    /// Require at least one character that is neither a letter nor a digit
+    #[arg(long, env = "PASSWORD_REQUIRE_SYMBOL", action = ArgAction::Set)]
+    pub password_require_symbol: Option<bool>,
+
+    /// Page the password reset mail links to, the token is added as ?token=
+    #[arg(long, env = "PASSWORD_RESET_URL")]
+    pub password_reset_url: Option<String>,
+    /// How long a password reset token stays valid
+    #[arg(long, env = "PASSWORD_RESET_LIFETIME_MINUTES")]
+    pub password_reset_lifetime_minutes: Option<i64>,
+
+    /// Where mail such as password resets goes: log, file or smtp
+    #[arg(long, env = "MAIL_PROVIDER", value_enum)]
+    pub mail_provider: Option<MailProvider>,
+    /// Sender of every mail, e.g. "Questions <no-reply@example.com>"
+    #[arg(long, env = "MAIL_FROM")]
+    pub mail_from: Option<String>,
+    /// Directory the file provider writes one .eml file per mail to
+    #[arg(long, env = "MAIL_DIR")]
+    pub mail_dir: Option<PathBuf>,
+    #[arg(long, env = "SMTP_HOST")]
+    pub smtp_host: Option<String>,
+    #[arg(long, env = "SMTP_PORT")]
+    pub smtp_port: Option<u16>,
+    #[arg(long, env = "SMTP_USER")]
+    pub smtp_user: Option<String>,
+    #[arg(long, env = "SMTP_PASSWORD", hide_env_values = true)]
+    pub smtp_password: Option<String>,
+    /// How the SMTP connection is secured: starttls, tls or none
+    #[arg(long, env = "SMTP_SECURITY", value_enum)]
+    pub smtp_security: Option<SmtpSecurity>,
+
+    /// Limit attempts on /login and /registration and lock accounts after failed logins
+    #[arg(long, env = "RATE_LIMIT_ENABLED", action = ArgAction::Set)]
+    pub rate_limit_enabled: Option<bool>,
+    /// Requests one client address may send to /login or /registration per window
+    #[arg(long, env = "RATE_LIMIT_IP_REQUESTS")]
+    pub rate_limit_ip_requests: Option<u32>,
+    #[arg(long, env = "RATE_LIMIT_IP_WINDOW_SECONDS")]
+    pub rate_limit_ip_window_seconds: Option<u64>,
+    /// Attempts per email address and window on /login or /registration
+    #[arg(long, env = "RATE_LIMIT_EMAIL_REQUESTS")]
+    pub rate_limit_email_requests: Option<u32>,
+    #[arg(long, env = "RATE_LIMIT_EMAIL_WINDOW_SECONDS")]
+    pub rate_limit_email_window_seconds: Option<u64>,
+    /// Failed logins within the failure window that lock the account
+    #[arg(long, env = "RATE_LIMIT_MAX_FAILURES")]
+    pub rate_limit_max_failures: Option<u32>,
+    #[arg(long, env = "RATE_LIMIT_FAILURE_WINDOW_SECONDS")]
+    pub rate_limit_failure_window_seconds: Option<u64>,
+    /// How long a locked account refuses logins
+    #[arg(long, env = "RATE_LIMIT_LOCKOUT_SECONDS")]
+    pub rate_limit_lockout_seconds: Option<u64>,
+    /// Take the client address from X-Forwarded-For, only safe behind a proxy that sets it
+    #[arg(long, env = "RATE_LIMIT_TRUST_PROXY", action = ArgAction::Set)]
+    pub rate_limit_trust_proxy: Option<bool>,
+
+    /// Which moderator checks posted text: none, word-list or http
+    #[arg(long, env = "MODERATION_PROVIDER", value_enum)]
+    pub moderation_provider: Option<ModerationProvider>,
+    /// What happens to text with offending words: censor, reject or flag
+    #[arg(long, env = "MODERATION_MODE", value_enum)]
+    pub moderation_mode: Option<ModerationMode>,
+    /// Base URL of the bad words API used by the http moderator
+    #[arg(long, env = "MODERATION_URL")]
+    pub moderation_url: Option<String>,
+    #[arg(long, env = "MODERATION_API_KEY", hide_env_values = true)]
+    pub moderation_api_key: Option<String>,
+    #[arg(long, env = "MODERATION_TIMEOUT_SECONDS")]
+    pub moderation_timeout_seconds: Option<u64>,
+    /// Word list for the word-list moderator, one word per line
+    #[arg(long, env = "MODERATION_WORD_LIST")]
+    pub moderation_word_list: Option<PathBuf>,
+}
+
+// What the binary should do, serving the API is the default
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Run the REST API
+    Serve,
+    /// Load seed questions into the database, then exit
+    Seed {
+        /// JSON file to load instead of the built in questions
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Give an account a role, e.g. to create the first admin, then exit
+    SetRole {
+        #[arg(long)]
+        email: String,
+        #[arg(long, value_enum)]
+        role: Role,
+    },
+}
+
+// Layout of the optional config file
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    #[serde(default)]
+    pub database: FileDatabase,
+    #[serde(default)]
+    pub server: FileServer,
+    #[serde(default)]
+    pub token: FileToken,
+    #[serde(default)]
+    pub seed: FileSeed,
+    #[serde(default)]
+    pub questions: FileQuestions,
+    #[serde(default)]
+    pub password: FilePassword,
+    #[serde(default)]
+    pub moderation: FileModeration,
+    #[serde(default)]
+    pub rate_limit: FileRateLimit,
+    #[serde(default)]
+    pub mail: FileMail,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FileDatabase {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub name: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub password_file: Option<PathBuf>,
+    pub max_connections: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FileServer {
+    pub bind_address: Option<String>,
+    pub allowed_origins: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FileToken {
+    pub secret: Option<String>,
+    pub secret_file: Option<PathBuf>,
+    pub lifetime_minutes: Option<i64>,
+    pub refresh_lifetime_days: Option<i64>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FileSeed {
+    pub on_startup: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FileQuestions {
+    pub soft_delete: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FilePassword {
+    pub min_length: Option<usize>,
+    pub require_mixed_case: Option<bool>,
+    pub require_digit: Option<bool>,
+    pub require_symbol: Option<bool>,
+    pub reset_url: Option<String>,
+    pub reset_lifetime_minutes: Option<i64>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FileModeration {
+    pub provider: Option<ModerationProvider>,
+    pub mode: Option<ModerationMode>,
+    pub url: Option<String>,
+    pub api_key: Option<String>,
+    pub timeout_seconds: Option<u64>,
+    pub word_list: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FileMail {
+    pub provider: Option<MailProvider>,
+    pub from: Option<String>,
+    pub dir: Option<PathBuf>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_user: Option<String>,
+    pub smtp_password: Option<String>,
+    pub smtp_security: Option<SmtpSecurity>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FileRateLimit {
+    pub enabled: Option<bool>,
+    pub ip_requests: Option<u32>,
+    pub ip_window_seconds: Option<u64>,
+    pub email_requests: Option<u32>,
+    pub email_window_seconds: Option<u64>,
+    pub max_failures: Option<u32>,
+    pub failure_window_seconds: Option<u64>,
+    pub lockout_seconds: Option<u64>,
+    pub trust_proxy: Option<bool>,
+}
+
+// Validated settings used by the rest of the application
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub bind_address: SocketAddr,
+    pub allowed_origins: Vec<String>,
+    pub token: TokenConfig,
+    pub seed_on_startup: bool,
+    // Deleting a question only marks it, POST /questions/:id/restore brings it back
+    pub soft_delete: bool,
+    pub password_policy: PasswordPolicy,
+    pub password_reset: PasswordResetConfig,
+    pub moderation: ModerationConfig,
+    pub rate_limit: RateLimitConfig,
+    pub mail: MailConfig,
+}
+
+#[derive(Clone)]
+pub struct DatabaseConfig {
+    pub host: String,
+    pub port: u16,
+    pub name: String,
+    pub user: String,
+    pub password: String,
+    pub max_connections: u32,
+}
+
+// Keep the password out of the logs
+impl fmt::Debug for DatabaseConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatabaseConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("name", &self.name)
+            .field("user", &self.user)
+            .field("max_connections", &self.max_connections)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+pub struct TokenConfig {
+    pub secret: Vec<u8>,
+    pub lifetime: chrono::Duration,
+    pub refresh_lifetime: chrono::Duration,
+}
+
+impl fmt::Debug for TokenConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenConfig")
+            .field("lifetime", &self.lifetime)
+            .field("refresh_lifetime", &self.refresh_lifetime)
+            .finish()
+    }
+}
+
+// Rules every new password has to follow
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_mixed_case: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+}
+
+// Longer passwords only slow down hashing, nobody needs them
+pub const PASSWORD_MAX_LENGTH: usize = 128;
+
+impl PasswordPolicy {
+    // Lists every rule the password breaks, empty when it is fine
+    pub fn violations(&self, password: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        let length = password.chars().count();
+        if length < self.min_length {
+            problems.push(format!(
+                "password must be at least {} characters",
+                self.min_length
+            ));
+        }
+        if length > PASSWORD_MAX_LENGTH {
+            problems.push(format!(
+                "password must be at most {} characters",
+                PASSWORD_MAX_LENGTH
+            ));
+        }
+        if self.require_mixed_case
+            && !(password.chars().any(char::is_lowercase)
+                && password.chars().any(char::is_uppercase))
+        {
+            problems.push("password must contain lower and upper case letters".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            problems.push("password must contain a digit".to_string());
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            problems.push("password must contain a symbol".to_string());
+        }
+        problems
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordResetConfig {
+    pub url: String,
+    pub lifetime: chrono::Duration,
+}
+
+// Which moderator checks posted text
+#[derive(Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModerationProvider {
+    #[default]
+    None,
+    WordList,
+    Http,
+}
+
+#[derive(Clone)]
+pub struct ModerationConfig {
+    pub provider: ModerationProvider,
+    pub mode: ModerationMode,
+    pub url: String,
+    pub api_key: Option<String>,
+    pub timeout: Duration,
+    pub word_list: Option<PathBuf>,
+}
+
+impl fmt::Debug for ModerationConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModerationConfig")
+            .field("provider", &self.provider)
+            .field("mode", &self.mode)
+            .field("url", &self.url)
+            .field("timeout", &self.timeout)
+            .field("word_list", &self.word_list)
+            .finish()
+    }
+}
+
+// Where mail goes
+#[derive(Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MailProvider {
+    // Written to the log, reset links included, only ever pick it for local development
+    Log,
+    // Written to MAIL_DIR, one file per mail
+    #[default]
+    File,
+    Smtp,
+}
+
+#[derive(Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmtpSecurity {
+    // Plain connection upgraded with STARTTLS, usually port 587
+    #[default]
+    Starttls,
+    // TLS from the start, usually port 465
+    Tls,
+    // No encryption at all, only for a relay on the same host
+    None,
+}
+
+impl SmtpSecurity {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SmtpSecurity::Starttls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MailConfig {
+    pub provider: MailProvider,
+    pub from: String,
+    pub dir: PathBuf,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_user: Option<String>,
+    pub smtp_password: Option<String>,
+    pub smtp_security: SmtpSecurity,
+}
+
+impl fmt::Debug for MailConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MailConfig")
+            .field("provider", &self.provider)
+            .field("from", &self.from)
+            .field("dir", &self.dir)
+            .field("smtp_host", &self.smtp_host)
+            .field("smtp_port", &self.smtp_port)
+            .field("smtp_user", &self.smtp_user)
+            .field("smtp_security", &self.smtp_security)
+            .finish()
+    }
+}
+
+// How many requests are allowed per window
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub window: Duration,
+}
+
+// Limits on /login and /registration and the lockout after failed logins
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub per_ip: RateLimit,
+    pub per_email: RateLimit,
+    pub max_failures: u32,
+    pub failure_window: Duration,
+    pub lockout: Duration,
+    pub trust_proxy: bool,
+}
+
+// Every problem found while loading the configuration
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn from_args(args: Args) -> Result<Config, ConfigError> {
+        let file = match &args.config_file {
+            Some(path) => read_file(path)?,
+            None => FileConfig::default(),
+        };
+        let mut errors = Vec::new();
+
+        let password_file = args.db_password_file.or(file.database.password_file);
+        let password = match (args.db_password.or(file.database.password), password_file) {
+            (Some(password), _) => password,
+            (None, Some(path)) => match std::fs::read_to_string(&path) {
+                Ok(contents) => contents.trim().to_string(),
+                Err(e) => {
+                    errors.push(format!(
+                        "could not read database password file {}: {}",
+                        path.display(),
+                        e
+                    ));
+                    String::new()
+                }
+            },
+            (None, None) => {
+                errors.push("database password is not set (PG_PASSWORD or PG_PASSWORDFILE)".into());
+                String::new()
+            }
+        };
+
+        let max_connections = args
+            .db_max_connections
+            .or(file.database.max_connections)
+            .unwrap_or(5);
+        if max_connections == 0 {
+            errors.push("database max connections must be at least 1".into());
+        }
+
+        let database = DatabaseConfig {
+            host: args
+                .db_host
+                .or(file.database.host)
+                .unwrap_or_else(|| "localhost".into()),
+            port: args.db_port.or(file.database.port).unwrap_or(5432),
+            name: args
+                .db_name
+                .or(file.database.name)
+                .unwrap_or_else(|| "questions".into()),
+            user: args
+                .db_user
+                .or(file.database.user)
+                .unwrap_or_else(|| "postgres".into()),
+            password,
+            max_connections,
+        };
+
+        let bind_address = args
+            .bind_address
+            .or(file.server.bind_address)
+            .unwrap_or_else(|| "0.0.0.0:8000".into());
+        let bind_address = match bind_address.parse::<SocketAddr>() {
+            Ok(address) => address,
+            Err(_) => {
+                errors.push(format!(
+                    "bind address {:?} is not a valid socket address",
+                    bind_address
+                ));
+                SocketAddr::from(([0, 0, 0, 0], 8000))
+            }
+        };
+
+        let allowed_origins = args
+            .allowed_origins
+            .or(file.server.allowed_origins)
+            .unwrap_or_else(|| vec!["http://127.0.0.1:3000".into()]);
+        for origin in &allowed_origins {
+            if origin.parse::<axum::http::HeaderValue>().is_err()
+                || !(origin.starts_with("http://") || origin.starts_with("https://"))
+            {
+                errors.push(format!("allowed origin {:?} is not a valid origin", origin));
+            }
+        }
+
+        // There is no default key, a known one would let anybody sign their own tokens
+        let secret_file = args.token_secret_file.or(file.token.secret_file);
+        let secret = match (args.token_secret.or(file.token.secret), secret_file) {
+            (Some(secret), _) => Some(secret),
+            // Only the line break is dropped, spaces can be part of the key
+            (None, Some(path)) => match std::fs::read_to_string(&path) {
+                Ok(contents) => Some(contents.trim_end_matches(['\r', '\n']).to_string()),
+                Err(e) => {
+                    errors.push(format!(
+                        "could not read token secret file {}: {}",
+                        path.display(),
+                        e
+                    ));
+                    None
+                }
+            },
+            (None, None) => {
+                errors.push("token secret is not set (TOKEN_SECRET or TOKEN_SECRET_FILE)".into());
+                None
+            }
+        };
+        if let Some(secret) = secret.as_ref().filter(|secret| secret.len() != 32) {
+            errors.push(format!(
+                "token secret must be exactly 32 bytes, got {}",
+                secret.len()
+            ));
+        }
+        let secret = secret.unwrap_or_default();
+
+        let lifetime_minutes = args
+            .token_lifetime_minutes
+            .or(file.token.lifetime_minutes)
+            .unwrap_or(15);
+        if lifetime_minutes <= 0 {
+            errors.push("token lifetime must be a positive number of minutes".into());
+        }
+
+        let refresh_lifetime_days = args
+            .refresh_token_lifetime_days
+            .or(file.token.refresh_lifetime_days)
+            .unwrap_or(30);
+        if refresh_lifetime_days <= 0 {
+            errors.push("refresh token lifetime must be a positive number of days".into());
+        }
+
+        let password_policy = PasswordPolicy {
+            min_length: args
+                .password_min_length
+                .or(file.password.min_length)
+                .unwrap_or(8),
+            require_mixed_case: args
+                .password_require_mixed_case
+                .or(file.password.require_mixed_case)
+                .unwrap_or(false),
+            require_digit: args
+                .password_require_digit
+                .or(file.password.require_digit)
+                .unwrap_or(false),
+            require_symbol: args
+                .password_require_symbol
+                .or(file.password.require_symbol)
+                .unwrap_or(false),
+        };
+        if password_policy.min_length == 0 || password_policy.min_length > PASSWORD_MAX_LENGTH {
+            errors.push(format!(
+                "password minimum length must be between 1 and {}",
+                PASSWORD_MAX_LENGTH
+            ));
+        }
+
+        let password_reset = PasswordResetConfig {
+            url: args
+                .password_reset_url
+                .or(file.password.reset_url)
+                .unwrap_or_else(|| "http://127.0.0.1:3000/reset-password".into()),
+            lifetime: chrono::Duration::minutes(
+                args.password_reset_lifetime_minutes
+                    .or(file.password.reset_lifetime_minutes)
+                    .unwrap_or(60),
+            ),
+        };
+        if !(password_reset.url.starts_with("http://")
+            || password_reset.url.starts_with("https://"))
+        {
+            errors.push(format!(
+                "password reset url {:?} is not a valid URL",
+                password_reset.url
+            ));
+        }
+        if password_reset.lifetime <= chrono::Duration::zero() {
+            errors.push("password reset lifetime must be a positive number of minutes".into());
+        }
+
+        let moderation = ModerationConfig {
+            provider: args
+                .moderation_provider
+                .or(file.moderation.provider)
+                .unwrap_or_default(),
+            mode: args
+                .moderation_mode
+                .or(file.moderation.mode)
+                .unwrap_or_default(),
+            url: args
+                .moderation_url
+                .or(file.moderation.url)
+                .unwrap_or_else(|| "https://api.apilayer.com".into()),
+            api_key: args.moderation_api_key.or(file.moderation.api_key),
+            timeout: Duration::from_secs(
+                args.moderation_timeout_seconds
+                    .or(file.moderation.timeout_seconds)
+                    .unwrap_or(5),
+            ),
+            word_list: args.moderation_word_list.or(file.moderation.word_list),
+        };
+        if moderation.provider == ModerationProvider::Http {
+            if !(moderation.url.starts_with("http://") || moderation.url.starts_with("https://")) {
+                errors.push(format!(
+                    "moderation url {:?} is not a valid URL",
+                    moderation.url
+                ));
+            }
+            if moderation.timeout.is_zero() {
+                errors.push("moderation timeout must be at least 1 second".into());
+            }
+        }
+        if let Some(path) = &moderation.word_list {
+            if !path.is_file() {
+                errors.push(format!(
+                    "moderation word list {} does not exist",
+                    path.display()
+                ));
+            }
+        }
+
+        let rate_limit = RateLimitConfig {
+            enabled: args
+                .rate_limit_enabled
+                .or(file.rate_limit.enabled)
+                .unwrap_or(true),
+            per_ip: RateLimit {
+                requests: args
+                    .rate_limit_ip_requests
+                    .or(file.rate_limit.ip_requests)
+                    .unwrap_or(20),
+                window: Duration::from_secs(
+                    args.rate_limit_ip_window_seconds
+                        .or(file.rate_limit.ip_window_seconds)
+                        .unwrap_or(60),
+                ),
+            },
+            per_email: RateLimit {
+                requests: args
+                    .rate_limit_email_requests
+                    .or(file.rate_limit.email_requests)
+                    .unwrap_or(5),
+                window: Duration::from_secs(
+                    args.rate_limit_email_window_seconds
+                        .or(file.rate_limit.email_window_seconds)
+                        .unwrap_or(60),
+                ),
+            },
+            max_failures: args
+                .rate_limit_max_failures
+                .or(file.rate_limit.max_failures)
+                .unwrap_or(5),
+            failure_window: Duration::from_secs(
+                args.rate_limit_failure_window_seconds
+                    .or(file.rate_limit.failure_window_seconds)
+                    .unwrap_or(900),
+            ),
+            lockout: Duration::from_secs(
+                args.rate_limit_lockout_seconds
+                    .or(file.rate_limit.lockout_seconds)
+                    .unwrap_or(900),
+            ),
+            trust_proxy: args
+                .rate_limit_trust_proxy
+                .or(file.rate_limit.trust_proxy)
+                .unwrap_or(false),
+        };
+        if rate_limit.per_ip.requests == 0 || rate_limit.per_email.requests == 0 {
+            errors.push("rate limit requests must be at least 1".into());
+        }
+        if rate_limit.max_failures == 0 {
+            errors.push("rate limit max failures must be at least 1".into());
+        }
+        if rate_limit.per_ip.window.is_zero()
+            || rate_limit.per_email.window.is_zero()
+            || rate_limit.failure_window.is_zero()
+            || rate_limit.lockout.is_zero()
+        {
+            errors.push("rate limit windows and the lockout must be at least 1 second".into());
+        }
+
+        let smtp_security = args
+            .smtp_security
+            .or(file.mail.smtp_security)
+            .unwrap_or_default();
+        let mail = MailConfig {
+            provider: args
+                .mail_provider
+                .or(file.mail.provider)
+                .unwrap_or_default(),
+            from: args
+                .mail_from
+                .or(file.mail.from)
+                .unwrap_or_else(|| "Questions <no-reply@localhost>".into()),
+            dir: args
+                .mail_dir
+                .or(file.mail.dir)
+                .unwrap_or_else(|| "mail".into()),
+            smtp_host: args.smtp_host.or(file.mail.smtp_host).unwrap_or_default(),
+            smtp_port: args
+                .smtp_port
+                .or(file.mail.smtp_port)
+                .unwrap_or(smtp_security.default_port()),
+            smtp_user: args.smtp_user.or(file.mail.smtp_user),
+            smtp_password: args.smtp_password.or(file.mail.smtp_password),
+            smtp_security,
+        };
+        if mail.from.parse::<lettre::message::Mailbox>().is_err() {
+            errors.push(format!(
+                "mail sender {:?} is not a valid address",
+                mail.from
+            ));
+        }
+        if mail.provider == MailProvider::Smtp {
+            if mail.smtp_host.is_empty() {
+                errors.push("smtp host must be set for the smtp mail provider (SMTP_HOST)".into());
+            }
+            if mail.smtp_port == 0 {
+                errors.push("smtp port must be at least 1".into());
+            }
+            if mail.smtp_user.is_some() != mail.smtp_password.is_some() {
+                errors.push("smtp user and password must be set together".into());
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(ConfigError(errors));
+        }
+
+        Ok(Config {
+            database,
+            bind_address,
+            allowed_origins,
+            token: TokenConfig {
+                secret: secret.into_bytes(),
+                lifetime: chrono::Duration::minutes(lifetime_minutes),
+                refresh_lifetime: chrono::Duration::days(refresh_lifetime_days),
+            },
+            seed_on_startup: args
+                .seed_on_startup
+                .or(file.seed.on_startup)
+                .unwrap_or(true),
+            soft_delete: args
+                .soft_delete
+                .or(file.questions.soft_delete)
+                .unwrap_or(false),
+            password_policy,
+            password_reset,
+            moderation,
+            rate_limit,
+            mail,
+        })
+    }
+}
+
+fn read_file(path: &PathBuf) -> Result<FileConfig, ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        ConfigError(vec![format!(
+            "could not read config file {}: {}",
+            path.display(),
+            e
+        )])
+    })?;
+    toml::from_str(&contents).map_err(|e| {
+        ConfigError(vec![format!(
+            "could not parse config file {}: {}",
+            path.display(),
+            e
+        )])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            require_mixed_case: false,
+            require_digit: false,
+            require_symbol: false,
+        }
+    }
+
+    #[test]
+    fn default_policy_only_checks_length() {
+        assert!(policy().violations("abcdefgh").is_empty());
+        assert_eq!(
+            policy().violations("abcdefg"),
+            ["password must be at least 8 characters"]
+        );
+        assert_eq!(
+            policy().violations(&"a".repeat(PASSWORD_MAX_LENGTH + 1)),
+            ["password must be at most 128 characters"]
+        );
+    }
+
+    #[test]
+    fn length_counts_characters_not_bytes() {
+        assert!(policy().violations("äöüäöüäö").is_empty());
+        assert!(!policy().violations("äöüäöüä").is_empty());
+    }
+
+    #[test]
+    fn every_broken_rule_is_listed() {
+        let strict = PasswordPolicy {
+            min_length: 10,
+            require_mixed_case: true,
+            require_digit: true,
+            require_symbol: true,
+        };
+        assert_eq!(
+            strict.violations("abc"),
+            [
+                "password must be at least 10 characters",
+                "password must contain lower and upper case letters",
+                "password must contain a digit",
+                "password must contain a symbol",
+            ]
+        );
+        assert!(strict.violations("Abcdefgh1!").is_empty());
+    }
+
+    #[test]
+    fn each_rule_on_its_own() {
+        let rule = |apply: fn(&mut PasswordPolicy)| {
+            let mut policy = policy();
+            apply(&mut policy);
+            policy
+        };
+        let mixed = rule(|p| p.require_mixed_case = true);
+        assert!(!mixed.violations("abcdefgh").is_empty());
+        assert!(!mixed.violations("ABCDEFGH").is_empty());
+        assert!(mixed.violations("abcdEFGH").is_empty());
+
+        let digit = rule(|p| p.require_digit = true);
+        assert!(!digit.violations("abcdefgh").is_empty());
+        assert!(digit.violations("abcdefg1").is_empty());
+
+        let symbol = rule(|p| p.require_symbol = true);
+        assert!(!symbol.violations("abcdefg1").is_empty());
+        assert!(symbol.violations("abcdefg ").is_empty());
+        assert!(symbol.violations("abcdefg-").is_empty());
+    }
+}
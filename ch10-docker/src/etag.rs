@@ -0,0 +1,174 @@
+use axum::http::header::{CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH};
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::types::error::MyError;
+
+// Entity tags of questions look like "3-9f86d081884c7d65", the question version and a digest
+// of the body. The digest changes with answers and votes, the version only when the question
+// itself is edited, so If-Match only looks at the version.
+
+// What a write to a question expects to find
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfMatch {
+    // If-Match: *, or writes that do not take the header
+    Any,
+    // Versions read from the listed tags, the question has to be at one of them
+    Versions(Vec<i32>),
+}
+
+impl IfMatch {
+    // Reads If-Match, a write without it is refused
+    pub fn from_headers(headers: &HeaderMap) -> Result<IfMatch, MyError> {
+        if !headers.contains_key(IF_MATCH) {
+            return Err(MyError::PreconditionRequired);
+        }
+
+        let mut versions = Vec::new();
+        for tag in tags(headers, IF_MATCH) {
+            if tag == "*" {
+                return Ok(IfMatch::Any);
+            }
+            // If-Match compares strongly, weak tags never match
+            if let Some(version) = version_of(tag) {
+                versions.push(version);
+            }
+        }
+        Ok(IfMatch::Versions(versions))
+    }
+
+    pub fn matches(&self, version: i32) -> bool {
+        match self {
+            IfMatch::Any => true,
+            IfMatch::Versions(versions) => versions.contains(&version),
+        }
+    }
+
+    // Bound to `$n::integer[] IS NULL OR version = ANY($n)`, NULL lets every version through
+    pub fn versions(&self) -> Option<Vec<i32>> {
+        match self {
+            IfMatch::Any => None,
+            IfMatch::Versions(versions) => Some(versions.clone()),
+        }
+    }
+}
+
+// Every tag listed in the header, which may be repeated or hold a comma separated list
+fn tags(headers: &HeaderMap, name: HeaderName) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(name)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+}
+
+// "3-9f86d081884c7d65" and "3" both give version 3
+fn version_of(tag: &str) -> Option<i32> {
+    let tag = tag.strip_prefix('"')?.strip_suffix('"')?;
+    tag.split('-').next()?.parse().ok()
+}
+
+pub fn etag(version: i32, body: &[u8]) -> String {
+    let digest = hex::encode(Sha256::digest(body));
+    format!("\"{}-{}\"", version, &digest[..16])
+}
+
+// If-None-Match compares weakly, W/ in front of a tag is ignored
+fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    tags(headers, IF_NONE_MATCH).any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+// The body as JSON with its ETag, or 304 without a body when the client already has it
+pub fn conditional_json<T: Serialize>(
+    headers: &HeaderMap,
+    version: i32,
+    body: &T,
+) -> Result<Response, MyError> {
+    let bytes = serde_json::to_vec(body).map_err(|_| MyError::SerializationError)?;
+    let etag = etag(version, &bytes);
+    if not_modified(headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response());
+    }
+
+    Ok((
+        [(CONTENT_TYPE, "application/json".to_string()), (ETAG, etag)],
+        bytes,
+    )
+        .into_response())
+}
+
+// The body as JSON with its ETag, for responses to writes
+pub fn tagged_json<T: Serialize>(version: i32, body: &T) -> Result<Response, MyError> {
+    conditional_json(&HeaderMap::new(), version, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(IF_MATCH, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn missing_if_match_is_required() {
+        assert!(matches!(
+            IfMatch::from_headers(&HeaderMap::new()),
+            Err(MyError::PreconditionRequired)
+        ));
+    }
+
+    #[test]
+    fn star_matches_any_version() {
+        let expected = IfMatch::from_headers(&headers(&["*"])).unwrap();
+        assert_eq!(expected, IfMatch::Any);
+        assert!(expected.matches(17));
+        assert_eq!(expected.versions(), None);
+    }
+
+    #[test]
+    fn reads_versions_from_full_and_short_tags() {
+        let expected = IfMatch::from_headers(&headers(&["\"3-9f86d081884c7d65\", \"5\""])).unwrap();
+        assert_eq!(expected, IfMatch::Versions(vec![3, 5]));
+        assert!(expected.matches(3));
+        assert!(expected.matches(5));
+        assert!(!expected.matches(4));
+    }
+
+    #[test]
+    fn repeated_headers_are_combined() {
+        let expected = IfMatch::from_headers(&headers(&["\"1\"", "\"2-abc\""])).unwrap();
+        assert_eq!(expected.versions(), Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn weak_and_malformed_tags_never_match() {
+        let expected = IfMatch::from_headers(&headers(&["W/\"3-abc\", 3, \"x-1\", \"\""])).unwrap();
+        assert_eq!(expected, IfMatch::Versions(Vec::new()));
+        assert!(!expected.matches(3));
+        assert_eq!(expected.versions(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn star_wins_over_listed_tags() {
+        let expected = IfMatch::from_headers(&headers(&["\"3\", *"])).unwrap();
+        assert_eq!(expected, IfMatch::Any);
+    }
+
+    #[test]
+    fn etag_carries_the_version() {
+        let tag = etag(7, b"{}");
+        assert!(tag.starts_with("\"7-") && tag.ends_with('"'));
+        assert_eq!(version_of(&tag), Some(7));
+        assert_ne!(etag(7, b"{}"), etag(7, b"[]"));
+    }
+}
@@ -0,0 +1,156 @@
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{MailConfig, MailProvider, SmtpSecurity};
+use crate::types::error::MyError;
+
+// How long to wait for the SMTP server before giving up
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// One plain text mail
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Anything that can deliver mail
+#[axum::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: &Mail) -> Result<(), MyError>;
+}
+
+// Builds the mailer picked in the configuration
+pub fn from_config(config: &MailConfig) -> Result<Arc<dyn Mailer>, MyError> {
+    let from = parse_mailbox(&config.from)?;
+    Ok(match config.provider {
+        MailProvider::Log => {
+            tracing::warn!(
+                "mail goes to the log, reset links included, use it for development only"
+            );
+            Arc::new(LogMailer { from })
+        }
+        MailProvider::File => Arc::new(FileMailer::new(from, config.dir.clone())?),
+        MailProvider::Smtp => Arc::new(SmtpMailer::new(from, config)?),
+    })
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, MyError> {
+    address
+        .parse()
+        .map_err(|_| MyError::MailError(format!("{:?} is not a valid address", address)))
+}
+
+fn build_message(from: &Mailbox, mail: &Mail) -> Result<Message, MyError> {
+    Message::builder()
+        .from(from.clone())
+        .to(parse_mailbox(&mail.to)?)
+        .subject(&mail.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(mail.body.clone())
+        .map_err(|e| MyError::MailError(e.to_string()))
+}
+
+// Writes every mail to the log instead of sending it, for local development
+pub struct LogMailer {
+    from: Mailbox,
+}
+
+#[axum::async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), MyError> {
+        tracing::info!(
+            "mail from {} to {}, subject {:?}\n{}",
+            self.from,
+            mail.to,
+            mail.subject,
+            mail.body
+        );
+        Ok(())
+    }
+}
+
+// Writes every mail as an .eml file into a directory, for local development and tests
+pub struct FileMailer {
+    from: Mailbox,
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(from: Mailbox, dir: PathBuf) -> Result<FileMailer, MyError> {
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            MyError::MailError(format!("could not create {}: {}", dir.display(), e))
+        })?;
+        Ok(FileMailer { from, dir })
+    }
+}
+
+#[axum::async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), MyError> {
+        let message = build_message(&self.from, mail)?;
+        // Sorts by time, the address keeps mails sent in the same instant apart
+        let name = format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            mail.to
+                .replace(|c: char| !c.is_alphanumeric() && c != '@' && c != '.', "_")
+        );
+        let path = self.dir.join(name);
+        tokio::fs::write(&path, message.formatted())
+            .await
+            .map_err(|e| MyError::MailError(format!("could not write {}: {}", path.display(), e)))
+    }
+}
+
+// Sends mail through an SMTP server
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(from: Mailbox, config: &MailConfig) -> Result<SmtpMailer, MyError> {
+        let host = config.smtp_host.as_str();
+        let builder = match config.smtp_security {
+            SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                host,
+            )),
+        }
+        .map_err(|e| MyError::MailError(e.to_string()))?
+        .port(config.smtp_port)
+        .timeout(Some(SMTP_TIMEOUT));
+
+        let builder = match (&config.smtp_user, &config.smtp_password) {
+            (Some(user), Some(password)) => {
+                builder.credentials(Credentials::new(user.clone(), password.clone()))
+            }
+            _ => builder,
+        };
+
+        Ok(SmtpMailer {
+            from,
+            transport: builder.build(),
+        })
+    }
+}
+
+#[axum::async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), MyError> {
+        let message = build_message(&self.from, mail)?;
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| MyError::MailError(e.to_string()))?;
+        Ok(())
+    }
+}
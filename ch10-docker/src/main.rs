@@ -1,26 +1,72 @@
+mod config;
+mod etag;
+mod html;
+mod mailer;
+mod moderation;
+mod rate_limit;
 mod routes;
+mod seed;
+mod state;
 mod store;
 mod types;
 
+use crate::routes::account::{change_password, delete_account, get_profile};
+use crate::routes::admin::{export_questions, import_questions, seed_questions, set_account_role};
+use crate::routes::answer::accept_answer;
 use crate::routes::answer::add_answer;
+use crate::routes::answer::delete_answer;
+use crate::routes::answer::get_answer;
+use crate::routes::answer::get_answers;
+use crate::routes::answer::unaccept_answer;
+use crate::routes::answer::update_answer;
+use crate::routes::authentication::login;
+use crate::routes::authentication::register;
+use crate::routes::authentication::{logout, logout_all, refresh};
+use crate::routes::moderation::{
+    approve_answer, approve_question, get_hidden_posts, get_question_for_review, get_review_queue,
+    hide_answer, hide_question, remove_answer, remove_question, report_answer, report_question,
+};
+use crate::routes::password::{forgot_password, reset_password};
 use crate::routes::question::add_question;
 use crate::routes::question::delete_question;
+use crate::routes::question::get_question;
+use crate::routes::question::get_question_frontend;
 use crate::routes::question::get_questions;
 use crate::routes::question::get_questions_frontend;
+use crate::routes::question::get_questions_json;
 
 use crate::routes::question::handler_fallback;
+use crate::routes::question::restore_question;
+use crate::routes::question::search_questions;
 use crate::routes::question::update_question;
+use crate::routes::revision::{
+    diff_answer_revisions, diff_question_revisions, get_answer_revisions, get_question_revisions,
+    rollback_answer, rollback_question,
+};
+use crate::routes::tags::get_tags;
+use crate::routes::vote::{unvote_answer, unvote_question, vote_answer, vote_question};
 
+use crate::config::{Args, Command, Config};
+use crate::moderation::Moderation;
+use crate::rate_limit::{limit_by_ip, RateLimiter};
+use crate::state::AppState;
 use crate::store::Store;
-use axum::http::{header, Method};
+use axum::extract::DefaultBodyLimit;
+use axum::middleware;
 use axum::routing::{delete, post, put};
 use axum::{routing::get, Router};
-//use sqlx::Executor;
+use clap::Parser;
 
+use axum::http::header::{ETAG, LOCATION};
+use axum::http::HeaderValue;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::fmt::format::FmtSpan;
 
+// Imports can be much larger than the default 2 MB request limit
+const IMPORT_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
 #[tokio::main]
 async fn main() {
     // Logging
@@ -33,43 +79,195 @@ async fn main() {
         .with_span_events(FmtSpan::CLOSE)
         .init();
 
-    let store: Store = store::Store::new().await;
+    // Settings come from flags, environment variables and an optional config file
+    let args = Args::parse();
+    let command = args.command.clone().unwrap_or(Command::Serve);
+    let config = match Config::from_args(args) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    tracing::info!("loaded {:?}", config);
+
+    let store: Store = store::Store::new(&config.database).await;
 
     sqlx::migrate!()
         .run(&store.clone().connection)
         .await
         .expect("Cannot run migration");
 
-    /*
-    // Set the sequence to start at a value higher than the maximum ID in the table
-    // Prevents duplicate IDs from being used
-    if let Err(err) = store
-        .connection
-        .execute("SELECT setval('answers_id_seq', (SELECT COALESCE(MAX(id), 0) FROM answers) + 1)")
-        .await
-    {
-        eprintln!("Failed to set sequence value: {}", err);
-        return;
+    // `seed` loads questions on demand and exits without serving
+    if let Command::Seed { file } = command {
+        match seed::load(file.as_deref()) {
+            Ok(questions) => match seed::seed(&store, &questions).await {
+                Ok(report) => {
+                    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+                    return;
+                }
+                Err(err) => eprintln!("Seeding failed: {:?}", err),
+            },
+            Err(err) => eprintln!("Seeding failed: {}", err.message()),
+        }
+        std::process::exit(1);
+    }
+
+    // `set-role` changes one account and exits without serving
+    if let Command::SetRole { email, role } = command {
+        let email = types::account::normalize_email(&email);
+        let result = match store.clone().get_account(email).await {
+            Ok(account) => {
+                store
+                    .set_account_role(&account.id.expect("id not found"), role)
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(account) => {
+                println!("{}", serde_json::to_string_pretty(&account).unwrap());
+                return;
+            }
+            Err(err) => eprintln!("Setting the role failed: {}", err.message()),
+        }
+        std::process::exit(1);
+    }
+
+    // Rows inserted with explicit ids in the past may have left the sequences behind
+    match store.connection.acquire().await {
+        Ok(mut connection) => {
+            if let Err(err) = seed::fix_sequences(&mut connection).await {
+                eprintln!("Failed to set sequence value: {:?}", err);
+                return;
+            }
+        }
+        Err(err) => {
+            eprintln!("Failed to set sequence value: {}", err);
+            return;
+        }
     }
-    */
+
+    if config.seed_on_startup {
+        match seed::seed_if_empty(&store).await {
+            Ok(Some(report)) => tracing::info!("seeded questions {:?}", report.inserted),
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("Seeding failed: {:?}", err);
+                return;
+            }
+        }
+    }
+
+    let moderation = match Moderation::from_config(&config.moderation) {
+        Ok(moderation) => moderation,
+        Err(err) => {
+            eprintln!("Failed to set up moderation: {}", err.message());
+            return;
+        }
+    };
+
+    let mailer = match mailer::from_config(&config.mail) {
+        Ok(mailer) => mailer,
+        Err(err) => {
+            eprintln!("Failed to set up mail: {}", err.message());
+            return;
+        }
+    };
+
+    let rate_limiter = RateLimiter::in_memory(config.rate_limit.clone());
+    let limit_ip = || middleware::from_fn_with_state(rate_limiter.clone(), limit_by_ip);
+
     let cors = CorsLayer::new()
-        .allow_origin(vec!["http://127.0.0.1:3000".parse().unwrap()])
+        .allow_origin(
+            config
+                .allowed_origins
+                .iter()
+                .map(|origin| origin.parse::<HeaderValue>().unwrap())
+                .collect::<Vec<_>>(),
+        )
         .allow_headers(Any)
-        .allow_methods(Any);
+        .allow_methods(Any)
+        // Browser clients need the ETag for If-Match and the Location of created posts
+        .expose_headers([ETAG, LOCATION]);
 
     let app = Router::new()
         .route("/api/v1/question", get(get_questions_frontend))
+        .route("/api/v1/question/:id", get(get_question_frontend))
+        .route("/api/v1/questions", get(get_questions_json))
         .route("/questions", get(get_questions))
         .route("/questions", post(add_question))
+        .route("/questions/search", get(search_questions))
+        .route("/questions/:id", get(get_question))
         .route("/questions/:id", put(update_question))
         .route("/questions/:id", delete(delete_question))
+        .route("/questions/:id/restore", post(restore_question))
+        .route("/questions/:id/answers", get(get_answers))
+        .route("/questions/:id/revisions", get(get_question_revisions))
+        .route(
+            "/questions/:id/revisions/diff",
+            get(diff_question_revisions),
+        )
+        .route(
+            "/questions/:id/revisions/:revision/rollback",
+            post(rollback_question),
+        )
+        .route("/questions/:id/vote", post(vote_question))
+        .route("/questions/:id/vote", delete(unvote_question))
+        .route("/questions/:id/report", post(report_question))
+        .route("/tags", get(get_tags))
         .route("/answer", post(add_answer))
+        .route("/answers/:id", get(get_answer))
+        .route("/answers/:id", put(update_answer))
+        .route("/answers/:id", delete(delete_answer))
+        .route("/answers/:id/revisions", get(get_answer_revisions))
+        .route("/answers/:id/revisions/diff", get(diff_answer_revisions))
+        .route(
+            "/answers/:id/revisions/:revision/rollback",
+            post(rollback_answer),
+        )
+        .route("/answers/:id/vote", post(vote_answer))
+        .route("/answers/:id/vote", delete(unvote_answer))
+        .route("/answers/:id/accept", post(accept_answer))
+        .route("/answers/:id/accept", delete(unaccept_answer))
+        .route("/answers/:id/report", post(report_answer))
+        .route("/registration", post(register).layer(limit_ip()))
+        .route("/login", post(login).layer(limit_ip()))
+        .route("/password/forgot", post(forgot_password).layer(limit_ip()))
+        .route("/password/reset", post(reset_password).layer(limit_ip()))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .route("/logout-all", post(logout_all))
+        .route("/account", get(get_profile))
+        .route("/account", delete(delete_account))
+        .route("/account/password", put(change_password))
+        .route("/admin/reports", get(get_review_queue))
+        .route("/admin/hidden", get(get_hidden_posts))
+        .route("/admin/questions/:id", get(get_question_for_review))
+        .route("/admin/questions/:id", delete(remove_question))
+        .route("/admin/questions/:id/approve", post(approve_question))
+        .route("/admin/questions/:id/hide", post(hide_question))
+        .route("/admin/answers/:id", delete(remove_answer))
+        .route("/admin/answers/:id/approve", post(approve_answer))
+        .route("/admin/answers/:id/hide", post(hide_answer))
+        .route("/admin/seed", post(seed_questions))
+        .route("/admin/accounts/:id/role", put(set_account_role))
+        .route("/admin/export", get(export_questions))
+        .route(
+            "/admin/import",
+            post(import_questions).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
         .layer(cors)
-        .with_state(store)
+        .with_state(AppState {
+            store,
+            config: Arc::new(config.clone()),
+            moderation,
+            rate_limiter,
+            mailer,
+        })
         .fallback(handler_fallback);
 
-    let ip = SocketAddr::new([0, 0, 0, 0].into(), 8000);
-    let listener = match tokio::net::TcpListener::bind(ip).await {
+    let listener = match tokio::net::TcpListener::bind(config.bind_address).await {
         Ok(listener) => listener,
         Err(err) => {
             eprintln!("Failed to bind listener: {}", err);
@@ -78,7 +276,9 @@ async fn main() {
     };
 
     tracing::debug!("serving {}", listener.local_addr().unwrap());
-    if let Err(err) = axum::serve(listener, app).await {
+    // The peer address is needed for the per address rate limit
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    if let Err(err) = axum::serve(listener, service).await {
         eprintln!("Server error: {}", err);
     }
 }
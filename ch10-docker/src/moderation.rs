@@ -0,0 +1,358 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{ModerationConfig, ModerationProvider};
+use crate::types::error::MyError;
+
+// Words used by the offline filter when no word list file is configured
+const DEFAULT_WORD_LIST: &str = include_str!("bad_words.txt");
+
+// Character used to mask offending words, same as the apilayer default
+const CENSOR_CHARACTER: char = '*';
+
+// What a moderator found in one piece of text
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    // The offending words as they appeared in the text
+    pub words: Vec<String>,
+    // The text with every offending word masked
+    pub censored: String,
+}
+
+// Anything that can check text for offensive words
+#[axum::async_trait]
+pub trait Moderator: Send + Sync {
+    async fn check(&self, text: &str) -> Result<Verdict, MyError>;
+}
+
+// What happens to a post containing offending words
+#[derive(Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModerationMode {
+    // Store the post with the words masked
+    #[default]
+    Censor,
+    // Refuse the post with 422
+    Reject,
+    // Store the post unchanged and mark it for review
+    Flag,
+}
+
+// Outcome of moderating one piece of text
+#[derive(Debug, Clone)]
+pub struct Moderated {
+    pub text: String,
+    pub flagged: bool,
+}
+
+// The configured moderator and mode, shared by every handler that stores user text
+#[derive(Clone)]
+pub struct Moderation {
+    moderator: Option<Arc<dyn Moderator>>,
+    mode: ModerationMode,
+}
+
+impl Moderation {
+    pub fn from_config(config: &ModerationConfig) -> Result<Moderation, MyError> {
+        let moderator: Option<Arc<dyn Moderator>> = match config.provider {
+            ModerationProvider::None => None,
+            ModerationProvider::WordList => Some(Arc::new(match &config.word_list {
+                Some(path) => WordListModerator::from_file(path)?,
+                None => WordListModerator::default(),
+            })),
+            ModerationProvider::Http => Some(Arc::new(HttpModerator::new(
+                &config.url,
+                config.api_key.clone(),
+                config.timeout,
+            )?)),
+        };
+
+        Ok(Moderation {
+            moderator,
+            mode: config.mode,
+        })
+    }
+
+    // Runs the text through the moderator and applies the configured mode
+    pub async fn review(&self, text: String) -> Result<Moderated, MyError> {
+        let moderator = match &self.moderator {
+            Some(moderator) => moderator,
+            None => {
+                return Ok(Moderated {
+                    text,
+                    flagged: false,
+                })
+            }
+        };
+
+        let verdict = moderator.check(&text).await?;
+        if verdict.words.is_empty() {
+            return Ok(Moderated {
+                text,
+                flagged: false,
+            });
+        }
+
+        match self.mode {
+            ModerationMode::Censor => Ok(Moderated {
+                text: verdict.censored,
+                flagged: false,
+            }),
+            ModerationMode::Reject => Err(MyError::ContentRejected(verdict.words)),
+            ModerationMode::Flag => Ok(Moderated {
+                text,
+                flagged: true,
+            }),
+        }
+    }
+}
+
+// Offline filter matching whole words against a list, ignoring case
+pub struct WordListModerator {
+    words: HashSet<String>,
+}
+
+impl WordListModerator {
+    // One word per line, blank lines and lines starting with # are ignored
+    pub fn parse(list: &str) -> WordListModerator {
+        WordListModerator {
+            words: list
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect(),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<WordListModerator, MyError> {
+        let list = std::fs::read_to_string(path).map_err(|e| {
+            MyError::ValidationError(format!("could not read {}: {}", path.display(), e))
+        })?;
+        Ok(WordListModerator::parse(&list))
+    }
+
+    fn scan(&self, text: &str) -> Verdict {
+        let mut words = Vec::new();
+        let mut censored = String::with_capacity(text.len());
+        let mut word = String::new();
+
+        // Collect runs of letters, digits and apostrophes, then decide per run
+        for c in text.chars().chain(std::iter::once(' ')) {
+            if c.is_alphanumeric() || c == '\'' {
+                word.push(c);
+                continue;
+            }
+            if !word.is_empty() {
+                if self.words.contains(&word.to_lowercase()) {
+                    censored.extend(std::iter::repeat_n(CENSOR_CHARACTER, word.chars().count()));
+                    words.push(std::mem::take(&mut word));
+                } else {
+                    censored.push_str(&word);
+                    word.clear();
+                }
+            }
+            censored.push(c);
+        }
+        censored.pop();
+
+        Verdict { words, censored }
+    }
+}
+
+impl Default for WordListModerator {
+    fn default() -> Self {
+        WordListModerator::parse(DEFAULT_WORD_LIST)
+    }
+}
+
+#[axum::async_trait]
+impl Moderator for WordListModerator {
+    async fn check(&self, text: &str) -> Result<Verdict, MyError> {
+        Ok(self.scan(text))
+    }
+}
+
+// Client for the apilayer bad_words API, or anything answering the same way
+pub struct HttpModerator {
+    client: reqwest::Client,
+    endpoint: String,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BadWordsResponse {
+    #[serde(default)]
+    bad_words_list: Vec<BadWord>,
+    censored_content: String,
+}
+
+#[derive(Deserialize)]
+struct BadWord {
+    original: String,
+}
+
+impl HttpModerator {
+    pub fn new(
+        base_url: &str,
+        api_key: Option<String>,
+        timeout: Duration,
+    ) -> Result<HttpModerator, MyError> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(MyError::ExternalAPIError)?;
+
+        Ok(HttpModerator {
+            client,
+            endpoint: format!(
+                "{}/bad_words?censor_character={}",
+                base_url.trim_end_matches('/'),
+                CENSOR_CHARACTER
+            ),
+            api_key,
+        })
+    }
+}
+
+#[axum::async_trait]
+impl Moderator for HttpModerator {
+    async fn check(&self, text: &str) -> Result<Verdict, MyError> {
+        let mut request = self.client.post(&self.endpoint).body(text.to_string());
+        if let Some(api_key) = &self.api_key {
+            request = request.header("apikey", api_key);
+        }
+
+        let response = request
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<BadWordsResponse>()
+            .await?;
+
+        Ok(Verdict {
+            words: response
+                .bad_words_list
+                .into_iter()
+                .map(|word| word.original)
+                .collect(),
+            censored: response.censored_content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moderation(mode: ModerationMode) -> Moderation {
+        Moderation {
+            moderator: Some(Arc::new(WordListModerator::parse(
+                "# comment\ndarn\n\n  Heck  \n",
+            ))),
+            mode,
+        }
+    }
+
+    #[test]
+    fn parse_skips_comments_and_blank_lines() {
+        let moderator = WordListModerator::parse("# comment\ndarn\n\n  Heck  \n");
+        let mut words: Vec<&str> = moderator.words.iter().map(String::as_str).collect();
+        words.sort();
+        assert_eq!(words, ["darn", "heck"]);
+    }
+
+    #[test]
+    fn scan_ignores_case() {
+        let verdict = WordListModerator::parse("darn").scan("Darn it, DARN");
+        assert_eq!(verdict.words, ["Darn", "DARN"]);
+        assert_eq!(verdict.censored, "**** it, ****");
+    }
+
+    #[test]
+    fn scan_matches_whole_words_only() {
+        let verdict = WordListModerator::parse("darn").scan("darned darning undarn darn's");
+        assert!(verdict.words.is_empty());
+        assert_eq!(verdict.censored, "darned darning undarn darn's");
+    }
+
+    #[test]
+    fn scan_splits_on_punctuation() {
+        let verdict = WordListModerator::parse("darn").scan("(darn)darn!\ndarn");
+        assert_eq!(verdict.words, ["darn", "darn", "darn"]);
+        assert_eq!(verdict.censored, "(****)****!\n****");
+    }
+
+    #[test]
+    fn scan_masks_one_character_per_letter() {
+        let verdict = WordListModerator::parse("häßlich").scan("so häßlich");
+        assert_eq!(verdict.censored, "so *******");
+    }
+
+    #[test]
+    fn default_list_is_not_empty() {
+        assert!(!WordListModerator::default().words.is_empty());
+    }
+
+    #[tokio::test]
+    async fn clean_text_passes_in_every_mode() {
+        for mode in [
+            ModerationMode::Censor,
+            ModerationMode::Reject,
+            ModerationMode::Flag,
+        ] {
+            let moderated = moderation(mode)
+                .review("all fine".to_string())
+                .await
+                .unwrap();
+            assert_eq!(moderated.text, "all fine");
+            assert!(!moderated.flagged);
+        }
+    }
+
+    #[tokio::test]
+    async fn censor_masks_the_words() {
+        let moderated = moderation(ModerationMode::Censor)
+            .review("oh heck".to_string())
+            .await
+            .unwrap();
+        assert_eq!(moderated.text, "oh ****");
+        assert!(!moderated.flagged);
+    }
+
+    #[tokio::test]
+    async fn reject_lists_the_words() {
+        let error = moderation(ModerationMode::Reject)
+            .review("darn and heck".to_string())
+            .await
+            .unwrap_err();
+        match error {
+            MyError::ContentRejected(words) => assert_eq!(words, ["darn", "heck"]),
+            other => panic!("expected ContentRejected, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn flag_keeps_the_text() {
+        let moderated = moderation(ModerationMode::Flag)
+            .review("oh heck".to_string())
+            .await
+            .unwrap();
+        assert_eq!(moderated.text, "oh heck");
+        assert!(moderated.flagged);
+    }
+
+    #[tokio::test]
+    async fn no_moderator_lets_everything_through() {
+        let moderation = Moderation {
+            moderator: None,
+            mode: ModerationMode::Reject,
+        };
+        let moderated = moderation.review("darn".to_string()).await.unwrap();
+        assert_eq!(moderated.text, "darn");
+        assert!(!moderated.flagged);
+    }
+}
@@ -0,0 +1,425 @@
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::config::{RateLimit, RateLimitConfig};
+use crate::types::error::MyError;
+
+// Expired entries are dropped from the in memory store every this many calls
+const SWEEP_INTERVAL: usize = 1000;
+
+// Attempts counted under one key in the current window
+#[derive(Debug, Clone, Copy)]
+pub struct Hits {
+    pub count: u32,
+    // Time until the window ends and counting starts over
+    pub resets_in: Duration,
+}
+
+// Where attempts and lockouts are kept
+// The in memory store only sees one process, several instances need a shared backend
+#[axum::async_trait]
+pub trait LimitStore: Send + Sync {
+    // Counts one attempt under the key, a new window starts once the old one has passed
+    async fn hit(&self, key: &str, window: Duration) -> Result<Hits, MyError>;
+    // Forgets the attempts counted under the key
+    async fn reset(&self, key: &str) -> Result<(), MyError>;
+    // Refuses the key for the given time
+    async fn lock(&self, key: &str, duration: Duration) -> Result<(), MyError>;
+    // How much longer the key is refused, if at all
+    async fn locked_for(&self, key: &str) -> Result<Option<Duration>, MyError>;
+}
+
+#[derive(Default)]
+pub struct MemoryLimitStore {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    windows: HashMap<String, Window>,
+    locks: HashMap<String, Instant>,
+    calls: usize,
+}
+
+struct Window {
+    started: Instant,
+    length: Duration,
+    count: u32,
+}
+
+impl Window {
+    fn ends(&self) -> Instant {
+        self.started + self.length
+    }
+}
+
+impl MemoryState {
+    // Drops windows and locks that have run out, so the maps don't grow forever
+    fn sweep(&mut self, now: Instant) {
+        self.calls += 1;
+        if self.calls < SWEEP_INTERVAL {
+            return;
+        }
+        self.calls = 0;
+        self.windows.retain(|_, window| window.ends() > now);
+        self.locks.retain(|_, until| *until > now);
+    }
+}
+
+impl MemoryLimitStore {
+    fn state(&self) -> std::sync::MutexGuard<'_, MemoryState> {
+        // The state stays consistent even if a holder panicked, keep using it
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[axum::async_trait]
+impl LimitStore for MemoryLimitStore {
+    async fn hit(&self, key: &str, window: Duration) -> Result<Hits, MyError> {
+        let now = Instant::now();
+        let mut state = self.state();
+        state.sweep(now);
+
+        let entry = state.windows.entry(key.to_string()).or_insert(Window {
+            started: now,
+            length: window,
+            count: 0,
+        });
+        if entry.ends() <= now {
+            entry.started = now;
+            entry.length = window;
+            entry.count = 0;
+        }
+        entry.count = entry.count.saturating_add(1);
+
+        Ok(Hits {
+            count: entry.count,
+            resets_in: entry.ends().saturating_duration_since(now),
+        })
+    }
+
+    async fn reset(&self, key: &str) -> Result<(), MyError> {
+        self.state().windows.remove(key);
+        Ok(())
+    }
+
+    async fn lock(&self, key: &str, duration: Duration) -> Result<(), MyError> {
+        self.state()
+            .locks
+            .insert(key.to_string(), Instant::now() + duration);
+        Ok(())
+    }
+
+    async fn locked_for(&self, key: &str) -> Result<Option<Duration>, MyError> {
+        let now = Instant::now();
+        Ok(self
+            .state()
+            .locks
+            .get(key)
+            .filter(|until| **until > now)
+            .map(|until| until.saturating_duration_since(now)))
+    }
+}
+
+// The configured limits and the store keeping count, shared by the auth handlers
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn LimitStore>,
+    config: RateLimitConfig,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn LimitStore>, config: RateLimitConfig) -> RateLimiter {
+        RateLimiter { store, config }
+    }
+
+    pub fn in_memory(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter::new(Arc::new(MemoryLimitStore::default()), config)
+    }
+
+    async fn count(&self, key: String, limit: RateLimit) -> Result<(), MyError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let hits = self.store.hit(&key, limit.window).await?;
+        if hits.count > limit.requests {
+            return Err(MyError::TooManyRequests(hits.resets_in));
+        }
+        Ok(())
+    }
+
+    // Counts a request from the client address to the route
+    pub async fn check_ip(&self, route: &str, ip: IpAddr) -> Result<(), MyError> {
+        self.count(format!("ip:{}:{}", route, ip), self.config.per_ip)
+            .await
+    }
+
+    // Counts an attempt for the email address on the route
+    pub async fn check_email(&self, route: &str, email: &str) -> Result<(), MyError> {
+        self.count(format!("email:{}:{}", route, email), self.config.per_email)
+            .await
+    }
+
+    // Refuses logins to an account that is locked after too many failures
+    pub async fn check_lockout(&self, email: &str) -> Result<(), MyError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        match self.store.locked_for(&lock_key(email)).await? {
+            Some(remaining) => Err(MyError::TooManyRequests(remaining)),
+            None => Ok(()),
+        }
+    }
+
+    // Counts a failed login, the account is locked once too many fail within the window
+    // Unknown emails are counted the same way so a lockout says nothing about registration
+    pub async fn login_failed(&self, email: &str) -> Result<(), MyError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let hits = self
+            .store
+            .hit(&failure_key(email), self.config.failure_window)
+            .await?;
+        if hits.count >= self.config.max_failures {
+            self.store
+                .lock(&lock_key(email), self.config.lockout)
+                .await?;
+            self.store.reset(&failure_key(email)).await?;
+            tracing::warn!("locked {} after {} failed logins", email, hits.count);
+        }
+        Ok(())
+    }
+
+    // A successful login clears the failures counted so far
+    pub async fn login_succeeded(&self, email: &str) -> Result<(), MyError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        self.store.reset(&failure_key(email)).await
+    }
+
+    // The address the request came from, or the one the proxy saw when it is trusted
+    fn client_ip(&self, headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+        if !self.config.trust_proxy {
+            return peer;
+        }
+        // Clients can put anything at the front of X-Forwarded-For, only the last entry
+        // was added by the proxy itself
+        headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .last()
+            .and_then(|ip| ip.trim().parse().ok())
+            .unwrap_or(peer)
+    }
+}
+
+fn failure_key(email: &str) -> String {
+    format!("failures:{}", email)
+}
+
+fn lock_key(email: &str) -> String {
+    format!("lock:{}", email)
+}
+
+// Per address limit, layered on /login and /registration
+pub async fn limit_by_ip(
+    State(limiter): State<RateLimiter>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, MyError> {
+    let ip = limiter.client_ip(request.headers(), peer.ip());
+    limiter.check_ip(request.uri().path(), ip).await?;
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const SHORT: Duration = Duration::from_millis(50);
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: true,
+            per_ip: RateLimit {
+                requests: 2,
+                window: SHORT,
+            },
+            per_email: RateLimit {
+                requests: 2,
+                window: Duration::from_secs(60),
+            },
+            max_failures: 3,
+            failure_window: Duration::from_secs(60),
+            lockout: SHORT,
+            trust_proxy: false,
+        }
+    }
+
+    fn forwarded(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn too_many(result: Result<(), MyError>) -> bool {
+        matches!(result, Err(MyError::TooManyRequests(_)))
+    }
+
+    #[tokio::test]
+    async fn hits_count_up_within_a_window() {
+        let store = MemoryLimitStore::default();
+        let window = Duration::from_secs(60);
+        assert_eq!(store.hit("a", window).await.unwrap().count, 1);
+        assert_eq!(store.hit("a", window).await.unwrap().count, 2);
+        assert_eq!(store.hit("b", window).await.unwrap().count, 1);
+
+        let hits = store.hit("a", window).await.unwrap();
+        assert_eq!(hits.count, 3);
+        assert!(hits.resets_in <= window && hits.resets_in > Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn a_new_window_starts_once_the_old_one_passed() {
+        let store = MemoryLimitStore::default();
+        store.hit("a", SHORT).await.unwrap();
+        store.hit("a", SHORT).await.unwrap();
+        tokio::time::sleep(SHORT * 2).await;
+        assert_eq!(store.hit("a", SHORT).await.unwrap().count, 1);
+    }
+
+    #[tokio::test]
+    async fn reset_forgets_the_count() {
+        let store = MemoryLimitStore::default();
+        let window = Duration::from_secs(60);
+        store.hit("a", window).await.unwrap();
+        store.hit("a", window).await.unwrap();
+        store.reset("a").await.unwrap();
+        assert_eq!(store.hit("a", window).await.unwrap().count, 1);
+    }
+
+    #[tokio::test]
+    async fn locks_run_out() {
+        let store = MemoryLimitStore::default();
+        assert!(store.locked_for("a").await.unwrap().is_none());
+        store.lock("a", SHORT).await.unwrap();
+        let remaining = store.locked_for("a").await.unwrap().unwrap();
+        assert!(remaining <= SHORT);
+        tokio::time::sleep(SHORT * 2).await;
+        assert!(store.locked_for("a").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn requests_over_the_limit_are_refused_until_the_window_ends() {
+        let limiter = RateLimiter::in_memory(config());
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        assert!(limiter.check_ip("/login", ip).await.is_ok());
+        assert!(limiter.check_ip("/login", ip).await.is_ok());
+        assert!(too_many(limiter.check_ip("/login", ip).await));
+
+        // Other routes and addresses are counted separately
+        assert!(limiter.check_ip("/registration", ip).await.is_ok());
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        assert!(limiter.check_ip("/login", other).await.is_ok());
+
+        tokio::time::sleep(SHORT * 2).await;
+        assert!(limiter.check_ip("/login", ip).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn disabled_limiter_never_refuses() {
+        let limiter = RateLimiter::in_memory(RateLimitConfig {
+            enabled: false,
+            ..config()
+        });
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        for _ in 0..10 {
+            assert!(limiter.check_ip("/login", ip).await.is_ok());
+            limiter.login_failed("a@x.io").await.unwrap();
+        }
+        assert!(limiter.check_lockout("a@x.io").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn account_locks_at_the_failure_threshold() {
+        let limiter = RateLimiter::in_memory(config());
+        limiter.login_failed("a@x.io").await.unwrap();
+        limiter.login_failed("a@x.io").await.unwrap();
+        assert!(limiter.check_lockout("a@x.io").await.is_ok());
+
+        limiter.login_failed("a@x.io").await.unwrap();
+        assert!(too_many(limiter.check_lockout("a@x.io").await));
+        assert!(limiter.check_lockout("b@x.io").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn lockout_ends_and_counting_starts_over() {
+        let limiter = RateLimiter::in_memory(config());
+        for _ in 0..3 {
+            limiter.login_failed("a@x.io").await.unwrap();
+        }
+        assert!(too_many(limiter.check_lockout("a@x.io").await));
+
+        tokio::time::sleep(SHORT * 2).await;
+        assert!(limiter.check_lockout("a@x.io").await.is_ok());
+
+        // The failures before the lockout were forgotten
+        limiter.login_failed("a@x.io").await.unwrap();
+        limiter.login_failed("a@x.io").await.unwrap();
+        assert!(limiter.check_lockout("a@x.io").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn successful_login_clears_failures() {
+        let limiter = RateLimiter::in_memory(config());
+        limiter.login_failed("a@x.io").await.unwrap();
+        limiter.login_failed("a@x.io").await.unwrap();
+        limiter.login_succeeded("a@x.io").await.unwrap();
+        limiter.login_failed("a@x.io").await.unwrap();
+        limiter.login_failed("a@x.io").await.unwrap();
+        assert!(limiter.check_lockout("a@x.io").await.is_ok());
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_without_trust_proxy() {
+        let limiter = RateLimiter::in_memory(config());
+        let peer: IpAddr = "192.168.1.1".parse().unwrap();
+        assert_eq!(limiter.client_ip(&forwarded(&["203.0.113.7"]), peer), peer);
+    }
+
+    #[test]
+    fn trusted_proxy_uses_the_last_forwarded_entry() {
+        let limiter = RateLimiter::in_memory(RateLimitConfig {
+            trust_proxy: true,
+            ..config()
+        });
+        let peer: IpAddr = "192.168.1.1".parse().unwrap();
+        let proxy_added: IpAddr = "203.0.113.7".parse().unwrap();
+
+        let spoofed = forwarded(&["1.2.3.4, 5.6.7.8, 203.0.113.7"]);
+        assert_eq!(limiter.client_ip(&spoofed, peer), proxy_added);
+
+        let repeated = forwarded(&["1.2.3.4", "203.0.113.7"]);
+        assert_eq!(limiter.client_ip(&repeated, peer), proxy_added);
+
+        assert_eq!(limiter.client_ip(&HeaderMap::new(), peer), peer);
+        assert_eq!(
+            limiter.client_ip(&forwarded(&["1.2.3.4, junk"]), peer),
+            peer
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use std::result::Result::Ok;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::routes::authentication::{check_password_policy, hash_password, verify_password};
+use crate::store::Store;
+use crate::types::account::{AccountDeletion, AccountProfile, PasswordChange, Session};
+use crate::types::error::MyError;
+
+// The logged in account, without its password
+pub async fn get_profile(
+    session: Session,
+    State(store): State<Store>,
+) -> Result<Json<AccountProfile>, MyError> {
+    let account = store.get_account_by_id(&session.account_id).await?;
+
+    Ok(Json(AccountProfile {
+        id: session.account_id,
+        email: account.email,
+        role: account.role,
+    }))
+}
+
+// Changes the password after checking the current one
+// Every other login of the account is logged out, this one stays
+pub async fn change_password(
+    session: Session,
+    State(store): State<Store>,
+    State(config): State<Arc<Config>>,
+    Json(change): Json<PasswordChange>,
+) -> Result<StatusCode, MyError> {
+    let account = store.get_account_by_id(&session.account_id).await?;
+    if !verify_password(&account.password, change.current_password.as_bytes())? {
+        return Err(MyError::WrongPassword);
+    }
+    if change.new_password == change.current_password {
+        return Err(MyError::ValidationError(
+            "new password must differ from the current one".to_string(),
+        ));
+    }
+    check_password_policy(&config.password_policy, &change.new_password)?;
+
+    store
+        .update_password(
+            &session.account_id,
+            hash_password(change.new_password.as_bytes()),
+            session.family,
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Deletes the logged in account after checking its password
+pub async fn delete_account(
+    session: Session,
+    State(store): State<Store>,
+    Json(deletion): Json<AccountDeletion>,
+) -> Result<StatusCode, MyError> {
+    let account = store.get_account_by_id(&session.account_id).await?;
+    if !verify_password(&account.password, deletion.password.as_bytes())? {
+        return Err(MyError::WrongPassword);
+    }
+
+    store.delete_account(&session.account_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
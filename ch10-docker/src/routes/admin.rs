@@ -0,0 +1,446 @@
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use std::collections::{HashMap, HashSet};
+use std::result::Result::Ok;
+
+use crate::seed::{self, SeedReport};
+use crate::store::Store;
+use crate::types::account::{
+    normalize_email, AccountId, AccountProfile, Role, RoleChange, Session,
+};
+use crate::types::error::MyError;
+use crate::types::transfer::{ImportReport, ImportResult, ImportStatus, QuestionRecord};
+
+// Number of questions read from the database per export chunk
+const EXPORT_BATCH: i64 = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ndjson,
+    JsonArray,
+}
+
+// Where the export stream is, `None` once everything has been written
+struct ExportCursor {
+    after_id: i32,
+    written: usize,
+}
+
+// Streams every question with its answers and tags, admins only
+// ?format=ndjson (default) writes one record per line, ?format=json writes one JSON array
+pub async fn export_questions(
+    session: Session,
+    Query(params): Query<HashMap<String, String>>,
+    State(store): State<Store>,
+) -> Result<Response, MyError> {
+    session.require(Role::Admin)?;
+
+    let format = match params.get("format").map(String::as_str) {
+        None | Some("ndjson") => Format::Ndjson,
+        Some("json") => Format::JsonArray,
+        Some(_) => {
+            return Err(MyError::ValidationError(
+                "format must be either ndjson or json".to_string(),
+            ))
+        }
+    };
+
+    let start = Some(ExportCursor {
+        after_id: 0,
+        written: 0,
+    });
+    let stream = futures::stream::unfold(start, move |cursor| {
+        let store = store.clone();
+        async move {
+            let cursor = cursor?;
+            let records = match store.export_questions(cursor.after_id, EXPORT_BATCH).await {
+                Ok(records) => records,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "export failed: {:?}", e);
+                    return Some((Err(std::io::Error::other("export failed")), None));
+                }
+            };
+
+            let mut chunk = String::new();
+            if format == Format::JsonArray && cursor.written == 0 {
+                chunk.push('[');
+            }
+            for (i, record) in records.iter().enumerate() {
+                if format == Format::JsonArray && cursor.written + i > 0 {
+                    chunk.push(',');
+                }
+                chunk.push_str(&serde_json::to_string(record).unwrap_or_default());
+                if format == Format::Ndjson {
+                    chunk.push('\n');
+                }
+            }
+
+            let last_id = records.last().and_then(|record| record.id);
+            let next = match last_id {
+                Some(after_id) if records.len() as i64 == EXPORT_BATCH => Some(ExportCursor {
+                    after_id,
+                    written: cursor.written + records.len(),
+                }),
+                _ => {
+                    if format == Format::JsonArray {
+                        chunk.push(']');
+                    }
+                    None
+                }
+            };
+
+            Some((Ok(chunk), next))
+        }
+    });
+
+    let content_type = match format {
+        Format::Ndjson => "application/x-ndjson",
+        Format::JsonArray => "application/json",
+    };
+    let file_name = match format {
+        Format::Ndjson => "questions.ndjson",
+        Format::JsonArray => "questions.json",
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .body(Body::from_stream(stream))
+        .map_err(|_| MyError::SerializationError)
+}
+
+// Splits an upload into JSON values, either one array or records one after another
+fn parse_records(body: &str) -> Result<Vec<serde_json::Value>, MyError> {
+    if body.trim_start().starts_with('[') {
+        return serde_json::from_str(body)
+            .map_err(|e| MyError::ValidationError(format!("invalid JSON array: {}", e)));
+    }
+
+    let mut values = Vec::new();
+    for (index, value) in serde_json::Deserializer::from_str(body)
+        .into_iter::<serde_json::Value>()
+        .enumerate()
+    {
+        let value =
+            value.map_err(|e| MyError::ValidationError(format!("record {}: {}", index, e)))?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+// Imports questions in the export format, NDJSON or a JSON array, admins only
+// Every record is validated first, if any is invalid nothing is written and 422 is returned
+// Owners are matched by account_email, posts whose owner is not registered here get none
+pub async fn import_questions(
+    session: Session,
+    State(store): State<Store>,
+    body: String,
+) -> Result<Response, MyError> {
+    session.require(Role::Admin)?;
+
+    let values = parse_records(&body)?;
+
+    let mut records = Vec::with_capacity(values.len());
+    let mut results = Vec::with_capacity(values.len());
+    for (index, value) in values.into_iter().enumerate() {
+        let record = serde_json::from_value::<QuestionRecord>(value)
+            .map_err(|e| e.to_string())
+            .and_then(QuestionRecord::validated);
+        match record {
+            Ok(record) => {
+                results.push(ImportResult {
+                    index,
+                    id: record.id,
+                    status: ImportStatus::Valid,
+                    answers: record.answers.len(),
+                    error: None,
+                    warnings: Vec::new(),
+                });
+                records.push((index, record));
+            }
+            Err(error) => results.push(ImportResult {
+                index,
+                id: None,
+                status: ImportStatus::Invalid,
+                answers: 0,
+                error: Some(error),
+                warnings: Vec::new(),
+            }),
+        }
+    }
+
+    check_question_ids(&records, &mut results);
+    check_answer_ids(&store, &records, &mut results).await?;
+    resolve_owners(&store, &mut records, &mut results).await?;
+
+    if results
+        .iter()
+        .any(|result| result.status == ImportStatus::Invalid)
+    {
+        let report = ImportReport::new(results);
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, Json(report)).into_response());
+    }
+
+    let records: Vec<QuestionRecord> = records.into_iter().map(|(_, record)| record).collect();
+    let outcomes = store
+        .import_questions(&records, &session.account_id)
+        .await?;
+    for (result, (id, created)) in results.iter_mut().zip(outcomes) {
+        result.id = Some(id);
+        result.status = if created {
+            ImportStatus::Created
+        } else {
+            ImportStatus::Updated
+        };
+    }
+
+    Ok(Json(ImportReport::new(results)).into_response())
+}
+
+// Each question id may only appear once, later records would overwrite earlier ones
+fn check_question_ids(records: &[(usize, QuestionRecord)], results: &mut [ImportResult]) {
+    let mut seen = HashMap::new();
+    for (index, record) in records {
+        if let Some(id) = record.id {
+            if let Some(first) = seen.insert(id, *index) {
+                results[*index].reject(format!(
+                    "question id {} is used by record {} as well",
+                    id, first
+                ));
+            }
+        }
+    }
+}
+
+// An answer id may only be reused by the question it already belongs to
+// Ids taken twice in the file or by an answer to another question fail the record
+async fn check_answer_ids(
+    store: &Store,
+    records: &[(usize, QuestionRecord)],
+    results: &mut [ImportResult],
+) -> Result<(), MyError> {
+    let mut seen = HashMap::new();
+    for (index, record) in records {
+        for id in record.answers.iter().filter_map(|answer| answer.id) {
+            if let Some(first) = seen.insert(id, *index) {
+                results[*index].reject(format!(
+                    "answer id {} is used by record {} as well",
+                    id, first
+                ));
+            }
+        }
+    }
+
+    let ids: Vec<i32> = seen.into_keys().collect();
+    let taken = store.answer_questions(&ids).await?;
+    for (index, record) in records {
+        for id in record.answers.iter().filter_map(|answer| answer.id) {
+            match taken.get(&id) {
+                Some(question) if Some(*question) != record.id => {
+                    results[*index]
+                        .reject(format!("answer id {} belongs to question {}", id, question));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+// Sets account_id of every post from its account_email, a bare account_id is never trusted
+// as the same id can belong to somebody else in this database
+async fn resolve_owners(
+    store: &Store,
+    records: &mut [(usize, QuestionRecord)],
+    results: &mut [ImportResult],
+) -> Result<(), MyError> {
+    let emails: HashSet<String> = records
+        .iter()
+        .flat_map(|(_, record)| {
+            std::iter::once(&record.account_email)
+                .chain(record.answers.iter().map(|answer| &answer.account_email))
+        })
+        .flatten()
+        .map(|email| normalize_email(email))
+        .collect();
+    let emails: Vec<String> = emails.into_iter().collect();
+    let accounts = store.account_ids_by_email(&emails).await?;
+
+    for (index, record) in records.iter_mut() {
+        let warnings = &mut results[*index].warnings;
+        record.account_id = owner(
+            "question",
+            &record.account_email,
+            record.account_id,
+            &accounts,
+            warnings,
+        );
+        for answer in &mut record.answers {
+            let post = match answer.id {
+                Some(id) => format!("answer {}", id),
+                None => "answer".to_string(),
+            };
+            answer.account_id = owner(
+                &post,
+                &answer.account_email,
+                answer.account_id,
+                &accounts,
+                warnings,
+            );
+        }
+    }
+    Ok(())
+}
+
+fn owner(
+    post: &str,
+    email: &Option<String>,
+    account_id: Option<i32>,
+    accounts: &HashMap<String, i32>,
+    warnings: &mut Vec<String>,
+) -> Option<i32> {
+    match (email, account_id) {
+        (Some(email), _) => {
+            let owner = accounts.get(&normalize_email(email)).copied();
+            if owner.is_none() {
+                warnings.push(format!(
+                    "{} owner {} is not registered, imported without owner",
+                    post, email
+                ));
+            }
+            owner
+        }
+        (None, Some(id)) => {
+            warnings.push(format!(
+                "{} account_id {} ignored, owners are matched by account_email",
+                post, id
+            ));
+            None
+        }
+        (None, None) => None,
+    }
+}
+
+// Loads the built in questions, or the seed file sent as the body, admins only
+pub async fn seed_questions(
+    session: Session,
+    State(store): State<Store>,
+    body: String,
+) -> Result<Json<SeedReport>, MyError> {
+    session.require(Role::Admin)?;
+
+    let questions = if body.trim().is_empty() {
+        seed::load(None)?
+    } else {
+        seed::parse(&body)?
+    };
+    Ok(Json(seed::seed(&store, &questions).await?))
+}
+
+// Hands an account a new role, admins only and never their own
+pub async fn set_account_role(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+    Json(change): Json<RoleChange>,
+) -> Result<Json<AccountProfile>, MyError> {
+    session.require(Role::Admin)?;
+    if session.account_id.0 == id {
+        return Err(MyError::ValidationError(
+            "admins cannot change their own role".to_string(),
+        ));
+    }
+
+    Ok(Json(
+        store.set_account_role(&AccountId(id), change.role).await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(result: Result<Vec<serde_json::Value>, MyError>) -> String {
+        match result {
+            Err(MyError::ValidationError(message)) => message,
+            other => panic!("expected ValidationError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_a_json_array() {
+        let values = parse_records(" \n[{\"title\": \"a\"}, {\"title\": \"b\"}]").unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1]["title"], "b");
+    }
+
+    #[test]
+    fn reads_ndjson() {
+        let values = parse_records("{\"title\": \"a\"}\n\n{\"title\": \"b\"}\n").unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0]["title"], "a");
+        assert!(parse_records("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_ndjson_line_is_reported_with_its_line() {
+        let message = invalid(parse_records(
+            "{\"title\": \"a\"}\n{\"title\": \"b\"}\n{\"title\": \n",
+        ));
+        assert!(message.starts_with("record 2:"), "{}", message);
+        assert!(message.contains("line 4"), "{}", message);
+
+        let message = invalid(parse_records("{\"title\": \"a\"}\n{title: \"b\"}\n"));
+        assert!(message.starts_with("record 1:"), "{}", message);
+        assert!(message.contains("line 2"), "{}", message);
+    }
+
+    #[test]
+    fn malformed_array_is_refused() {
+        let message = invalid(parse_records("[{\"title\": \"a\"},]"));
+        assert!(message.starts_with("invalid JSON array"), "{}", message);
+    }
+
+    #[test]
+    fn repeated_question_ids_fail_the_later_records() {
+        let records: Vec<(usize, QuestionRecord)> =
+            [Some(1), None, Some(1), Some(2), None, Some(1)]
+                .into_iter()
+                .enumerate()
+                .map(|(index, id)| {
+                    let json = serde_json::json!({"id": id, "title": "T", "content": "C"});
+                    (index, serde_json::from_value(json).unwrap())
+                })
+                .collect();
+        let mut results: Vec<ImportResult> = records
+            .iter()
+            .map(|(index, record)| ImportResult {
+                index: *index,
+                id: record.id,
+                status: ImportStatus::Valid,
+                answers: 0,
+                error: None,
+                warnings: Vec::new(),
+            })
+            .collect();
+
+        check_question_ids(&records, &mut results);
+        let invalid: Vec<usize> = results
+            .iter()
+            .filter(|result| result.status == ImportStatus::Invalid)
+            .map(|result| result.index)
+            .collect();
+        assert_eq!(invalid, [2, 5]);
+        assert_eq!(
+            results[2].error.as_deref(),
+            Some("question id 1 is used by record 0 as well")
+        );
+    }
+}
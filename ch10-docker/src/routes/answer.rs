@@ -1,29 +1,165 @@
 use axum::body::Body;
 
+use axum::extract::{Path, Query};
 use axum::Json;
 use axum::{extract::State, http::StatusCode, response::Response};
+use std::collections::HashMap;
 use std::result::Result::Ok;
 
 extern crate serde_json;
+use crate::moderation::Moderation;
+use crate::routes::created;
+use crate::routes::question::sees_hidden;
 use crate::store::Store;
-use crate::types::pagination::MyError;
+use crate::types::account::{Role, Session};
+use crate::types::error::MyError;
+use crate::types::pagination::{extract_cursor_page, Page};
 
-use crate::types::answer::NewAnswer;
+use crate::types::answer::{Answer, NewAnswer, UpdateAnswer};
 
-// Adds answer to the database
-// _e was still flagging clippy so I added allow clause
-#[allow(unused_variables)]
+// Answers to one question, oldest first, paged with ?cursor=...&limit=...
+// Moderators also see hidden answers
+pub async fn get_answers(
+    session: Option<Session>,
+    Path(id): Path<i32>,
+    Query(params): Query<HashMap<String, String>>,
+    State(store): State<Store>,
+) -> Result<Json<Page<Answer>>, MyError> {
+    let page = extract_cursor_page(&params)?;
+    let include_hidden = sees_hidden(&session);
+    // Fails with 404 when the question does not exist or is hidden
+    store.get_question(id, include_hidden).await?;
+
+    Ok(Json(
+        store.get_answers_page(id, &page, include_hidden).await?,
+    ))
+}
+
+// Adds answer to the database, requires a logged in account
+// The question has to exist and be visible to the account, the content goes through moderation
 pub async fn add_answer(
+    session: Session,
     State(store): State<Store>,
+    State(moderation): State<Moderation>,
     Json(new_answer): Json<NewAnswer>,
 ) -> Result<Response, MyError> {
-    if let Err(_e) = store.add_answer(new_answer).await {
-        return Err(MyError::DatabaseQueryError);
+    let mut new_answer = new_answer.validated()?;
+    store
+        .get_question(new_answer.question_id.0, session.has_role(Role::Moderator))
+        .await?;
+
+    let content = moderation.review(new_answer.content).await?;
+    new_answer.content = content.text;
+
+    let answer = store
+        .add_answer(new_answer, session.account_id, content.flagged)
+        .await?;
+
+    Ok(created(format!("/answers/{}", answer.id.0), answer))
+}
+
+// A single answer, moderators also see hidden ones
+// Answers to a hidden question are missing for everyone else as well
+pub async fn get_answer(
+    session: Option<Session>,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Answer>, MyError> {
+    Ok(Json(
+        visible_answer(&store, id, sees_hidden(&session)).await?,
+    ))
+}
+
+// Looks up an answer the way GET /answers/:id shows it, missing when it or its question is hidden
+pub async fn visible_answer(
+    store: &Store,
+    id: i32,
+    include_hidden: bool,
+) -> Result<Answer, MyError> {
+    let answer = store.get_answer(id, include_hidden).await?;
+    match store
+        .get_question(answer.question_id.0, include_hidden)
+        .await
+    {
+        Ok(_) => Ok(answer),
+        Err(MyError::QuestionNotFound) => Err(MyError::AnswerNotFound),
+        Err(e) => Err(e),
+    }
+}
+
+// Changes the content of an answer, only its author may do this
+// The new content goes through moderation like a new answer
+pub async fn update_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+    State(moderation): State<Moderation>,
+    Json(update): Json<UpdateAnswer>,
+) -> Result<Json<Answer>, MyError> {
+    if !store.is_answer_owner(id, &session.account_id).await? {
+        return Err(MyError::Forbidden);
+    }
+    let update = update.validated()?;
+
+    let content = moderation.review(update.content).await?;
+    Ok(Json(
+        store
+            .update_answer(id, content.text, content.flagged, &session.account_id)
+            .await?,
+    ))
+}
+
+// Deletes a single answer, its author and moderators may do this
+pub async fn delete_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Response, MyError> {
+    if !session.has_role(Role::Moderator) && !store.is_answer_owner(id, &session.account_id).await?
+    {
+        return Err(MyError::Forbidden);
     }
+
+    store.delete_answer(id).await?;
+
     let response = Response::builder()
         .status(StatusCode::OK)
-        .body(Body::from("Answer Added"))
+        .body(Body::from(format!("Answer {} Deleted", id)))
         .unwrap();
 
     Ok(response)
 }
+
+// Marks an answer as the accepted one, only the question author may do this
+pub async fn accept_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Answer>, MyError> {
+    let answer = store.get_answer(id, false).await?;
+    if !store
+        .is_question_owner(answer.question_id.0, &session.account_id)
+        .await?
+    {
+        return Err(MyError::Forbidden);
+    }
+
+    Ok(Json(store.accept_answer(id, answer.question_id.0).await?))
+}
+
+// Removes the accepted mark again, only the question author may do this
+pub async fn unaccept_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Answer>, MyError> {
+    let answer = store.get_answer(id, false).await?;
+    if !store
+        .is_question_owner(answer.question_id.0, &session.account_id)
+        .await?
+    {
+        return Err(MyError::Forbidden);
+    }
+
+    Ok(Json(store.unaccept_answer(id).await?))
+}
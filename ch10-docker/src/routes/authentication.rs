@@ -1,100 +1,268 @@
-/*
-
-use crate::types::account::Account;
-use axum::body::Body;
+use crate::types::account::AccountId;
+use crate::types::account::Role;
+use crate::types::account::{normalize_email, validate_email, NewAccount};
+use crate::types::account::{Account, AccountProfile};
+use crate::types::token::{LogoutSummary, RefreshRequest, TokenPair};
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
 use axum::Json;
 use axum::{extract::State, http::StatusCode, response::Response};
+use chrono::prelude::*;
 use std::result::Result::Ok;
 extern crate serde_json;
+use crate::config::{Config, PasswordPolicy, TokenConfig};
+use crate::rate_limit::RateLimiter;
+use crate::routes::created;
 use crate::store::Store;
-use crate::types::pagination::MyError;
-use argon2::{self, Config};
+use crate::types::account::Session;
+use crate::types::error::MyError;
 use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, OnceLock};
 
-
-// Register and account and password
-// In progress, currently not being ran
-#[allow(dead_code)]
+// Registers an account and stores the hashed password
+// The email is lower cased and checked, the password has to follow the configured policy
 pub async fn register(
     State(store): State<Store>,
-    Json(account): Json<Account>,
+    State(config): State<Arc<Config>>,
+    State(limiter): State<RateLimiter>,
+    Json(new_account): Json<NewAccount>,
 ) -> Result<Response, MyError> {
-    let hashed_password = hash_password(account.password.as_bytes());
+    let email = normalize_email(&new_account.email);
+    validate_email(&email)?;
+    limiter.check_email("registration", &email).await?;
+    check_password_policy(&config.password_policy, &new_account.password)?;
+
+    let hashed_password = hash_password(new_account.password.as_bytes());
+    // New accounts are always plain users, roles are handed out by an admin
     let account = Account {
-        id: account.id,
-        email: account.email,
+        id: None,
+        email: email.clone(),
         password: hashed_password,
+        role: Role::User,
     };
 
-    if let Err(_e) = store.add_account(account).await {
-        return Err(MyError::QuestionNotFound);
-    }
-    let response = Response::builder()
-        .status(StatusCode::OK)
-        .body(Body::from("Account Added"))
-        .unwrap();
+    let id = store.add_account(account).await?;
+
+    Ok(created(
+        "/account".to_string(),
+        AccountProfile {
+            id,
+            email,
+            role: Role::User,
+        },
+    ))
+}
 
-    Ok(response)
+// Fails with every rule the password breaks
+pub fn check_password_policy(policy: &PasswordPolicy, password: &str) -> Result<(), MyError> {
+    let problems = policy.violations(password);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(MyError::ValidationError(problems.join(", ")))
+    }
 }
 
 pub fn hash_password(password: &[u8]) -> String {
     let salt = rand::thread_rng().gen::<[u8; 32]>();
-    let config = Config::default();
+    let config = argon2::Config::default();
     argon2::hash_encoded(password, &salt, &config).unwrap()
 }
 
-
+// Checks the password against the stored hash and hands back an access and a refresh token
+// Every login starts a new token family that /logout can revoke
+// Too many failed logins lock the account for a while, locked accounts get 429
 pub async fn login(
     State(store): State<Store>,
-    Json(login): Json<Account>,
-) -> Result<Response, MyError> {
-    let account_result = store.get_account(login.email).await;
+    State(config): State<Arc<Config>>,
+    State(limiter): State<RateLimiter>,
+    Json(login): Json<NewAccount>,
+) -> Result<Json<TokenPair>, MyError> {
+    let email = normalize_email(&login.email);
+    limiter.check_lockout(&email).await?;
+    limiter.check_email("login", &email).await?;
+
+    let account_result = store.clone().get_account(email.clone()).await;
     match account_result {
         Ok(account) => match verify_password(&account.password, login.password.as_bytes()) {
             Ok(verified) => {
                 if verified {
-                    let token = issue_token(account.id.expect("id not found"));
-                    let json_response =
-                        serde_json::to_string(&token).map_err(|_e| MyError::QuestionNotFound)?;
-                    Ok((StatusCode::OK, json_response).into_response())
+                    limiter.login_succeeded(&email).await?;
+                    let account_id = account.id.expect("id not found");
+                    let (refresh_token, refresh_hash) = new_token();
+                    let family = store
+                        .start_token_family(
+                            &account_id,
+                            &refresh_hash,
+                            refresh_expiry(&config.token),
+                        )
+                        .await?;
+                    Ok(Json(token_pair(
+                        &config.token,
+                        account_id,
+                        account.role,
+                        family,
+                        refresh_token,
+                    )))
                 } else {
-                    Err(MyError::QuestionNotFound)
+                    limiter.login_failed(&email).await?;
+                    Err(MyError::WrongPassword)
                 }
             }
-            Err(e) => Err(MyError::QuestionNotFound),
+            Err(e) => Err(MyError::ArgonLibraryError(e)),
         },
+        // Don't reveal whether the email is registered, not even by answering faster
+        Err(MyError::AccountNotFound) => {
+            verify_password(dummy_hash(), login.password.as_bytes())?;
+            limiter.login_failed(&email).await?;
+            Err(MyError::WrongPassword)
+        }
         Err(e) => Err(e),
     }
 }
 
-fn verify_password(hash: &str, password: &[u8]) -> Result<bool, argon2::Error> {
+// Trades a refresh token for a new pair, the old refresh token stops working
+// The role is read again, so role changes apply from the next refresh
+pub async fn refresh(
+    State(store): State<Store>,
+    State(config): State<Arc<Config>>,
+    Json(request): Json<RefreshRequest>,
+) -> Result<Json<TokenPair>, MyError> {
+    let (refresh_token, refresh_hash) = new_token();
+    let (account_id, role, family) = store
+        .rotate_refresh_token(
+            &hash_token(&request.refresh_token),
+            &refresh_hash,
+            refresh_expiry(&config.token),
+        )
+        .await?;
+
+    Ok(Json(token_pair(
+        &config.token,
+        account_id,
+        role,
+        family,
+        refresh_token,
+    )))
+}
+
+// Revokes the login the access token belongs to
+pub async fn logout(session: Session, State(store): State<Store>) -> Result<StatusCode, MyError> {
+    store.revoke_token_family(session.family).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Revokes every login of the account, on every device
+pub async fn logout_all(
+    session: Session,
+    State(store): State<Store>,
+) -> Result<Json<LogoutSummary>, MyError> {
+    let revoked = store.revoke_account_tokens(&session.account_id).await?;
+    Ok(Json(LogoutSummary { revoked }))
+}
+
+pub fn verify_password(hash: &str, password: &[u8]) -> Result<bool, argon2::Error> {
     argon2::verify_encoded(hash, password)
 }
 
-fn issue_token(account_id: AccountId) -> String {
+// Hash of a random password, verified against when the email is unknown so both cases take as long
+fn dummy_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash_password(&rand::thread_rng().gen::<[u8; 32]>()))
+}
+
+fn issue_token(config: &TokenConfig, account_id: AccountId, role: Role, family: i32) -> String {
     let current_date_time = Utc::now();
-    let dt = current_date_time + chrono::Duration::days(1);
+    let dt = current_date_time + config.lifetime;
     paseto::tokens::PasetoBuilder::new()
-        .set_encryption_key(&Vec::from("RANDOM WORDS WINTER MACINTOSH PC".as_bytes()))
+        .set_encryption_key(&config.secret)
         .set_expiration(&dt)
         .set_not_before(&Utc::now())
         .set_claim("account_id", serde_json::json!(account_id))
+        .set_claim("role", serde_json::json!(role))
+        .set_claim("family", serde_json::json!(family))
         .build()
         .expect("Failed to construct paseto token w/ builder!")
 }
 
-#[allow(clippy::needless_borrow)]
-#[allow(dead_code)]
-pub fn verify_token(token: String) -> Result<Session, MyError> {
+fn token_pair(
+    config: &TokenConfig,
+    account_id: AccountId,
+    role: Role,
+    family: i32,
+    refresh_token: String,
+) -> TokenPair {
+    TokenPair {
+        access_token: issue_token(config, account_id, role, family),
+        refresh_token,
+        token_type: "Bearer",
+        expires_in: config.lifetime.num_seconds(),
+    }
+}
+
+// A random refresh or password reset token and the hash that is stored for it
+pub fn new_token() -> (String, String) {
+    let token = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+    let hash = hash_token(&token);
+    (token, hash)
+}
+
+// The tokens are random, so a plain SHA-256 is enough to keep them out of the database
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.trim().as_bytes()))
+}
+
+fn refresh_expiry(config: &TokenConfig) -> NaiveDateTime {
+    (Utc::now() + config.refresh_lifetime).naive_utc()
+}
+
+// Checks the token and that its login has not been revoked
+pub async fn verify_token(
+    config: &TokenConfig,
+    store: &Store,
+    token: String,
+) -> Result<Session, MyError> {
     let token = paseto::tokens::validate_local_token(
         &token,
         None,
-        &"RANDOM WORDS WINTER MACINTOSH PC".as_bytes(),
+        &config.secret,
         &paseto::tokens::TimeBackend::Chrono,
     )
-    .map_err(|_| MyError::QuestionNotFound)?;
-    serde_json::from_value::<Session>(token).map_err(|_| MyError::QuestionNotFound)
+    .map_err(|_| MyError::Unauthorized)?;
+    let session = serde_json::from_value::<Session>(token).map_err(|_| MyError::Unauthorized)?;
+
+    if !store.is_token_family_active(session.family).await? {
+        return Err(MyError::Unauthorized);
+    }
+    Ok(session)
 }
 
+// Extracts a Session from the Authorization header
+// Any handler taking a Session rejects requests without a valid token
+#[async_trait]
+impl<S> FromRequestParts<S> for Session
+where
+    Arc<Config>: FromRef<S>,
+    Store: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = MyError;
 
-*/
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let header = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(MyError::Unauthorized)?;
+
+        // Accept both a bare token and the usual "Bearer <token>" form
+        let token = header.strip_prefix("Bearer ").unwrap_or(header).trim();
+
+        let config = Arc::<Config>::from_ref(state);
+        let store = Store::from_ref(state);
+        verify_token(&config.token, &store, token.to_string()).await
+    }
+}
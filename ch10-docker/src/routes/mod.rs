@@ -1,3 +1,20 @@
+use axum::http::{header::LOCATION, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+pub mod account;
+pub mod admin;
 pub mod answer;
 pub mod authentication;
+pub mod moderation;
+pub mod password;
 pub mod question;
+pub mod revision;
+pub mod tags;
+pub mod vote;
+
+// 201 Created pointing at the new resource, with the resource itself as the body
+pub fn created<T: Serialize>(location: String, body: T) -> Response {
+    (StatusCode::CREATED, [(LOCATION, location)], Json(body)).into_response()
+}
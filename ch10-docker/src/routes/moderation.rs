@@ -0,0 +1,148 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use std::result::Result::Ok;
+
+use crate::store::Store;
+use crate::types::account::{Role, Session};
+use crate::types::error::MyError;
+use crate::types::questions::QuestionWithAnswers;
+use crate::types::report::{NewReport, Report, Resolution, Resolved, ReviewItem};
+
+// Reports a question to the moderators, one open report per account
+pub async fn report_question(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+    Json(report): Json<NewReport>,
+) -> Result<(StatusCode, Json<Report>), MyError> {
+    let reason = report.validated()?;
+    let report = store
+        .report_question(id, &session.account_id, reason)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+// Reports an answer to the moderators, one open report per account
+pub async fn report_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+    Json(report): Json<NewReport>,
+) -> Result<(StatusCode, Json<Report>), MyError> {
+    let reason = report.validated()?;
+    let report = store.report_answer(id, &session.account_id, reason).await?;
+
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+// Flagged and reported posts waiting for a decision
+pub async fn get_review_queue(
+    session: Session,
+    State(store): State<Store>,
+) -> Result<Json<Vec<ReviewItem>>, MyError> {
+    session.require(Role::Moderator)?;
+    Ok(Json(store.get_review_queue().await?))
+}
+
+// Every hidden post, they no longer show up anywhere else
+pub async fn get_hidden_posts(
+    session: Session,
+    State(store): State<Store>,
+) -> Result<Json<Vec<ReviewItem>>, MyError> {
+    session.require(Role::Moderator)?;
+    Ok(Json(store.get_hidden_posts().await?))
+}
+
+// One question with all of its answers, hidden ones included
+pub async fn get_question_for_review(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<QuestionWithAnswers>, MyError> {
+    session.require(Role::Moderator)?;
+    let question = store.get_question(id, true).await?;
+    let score = store.question_score(id).await?;
+    let answers = store.get_answers_for_question(id, true).await?;
+
+    Ok(Json(QuestionWithAnswers::new(question, score, answers)))
+}
+
+pub async fn approve_question(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Resolved>, MyError> {
+    session.require(Role::Moderator)?;
+    Ok(Json(
+        store
+            .resolve_question(id, Resolution::Approved, &session.account_id)
+            .await?,
+    ))
+}
+
+pub async fn hide_question(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Resolved>, MyError> {
+    session.require(Role::Moderator)?;
+    Ok(Json(
+        store
+            .resolve_question(id, Resolution::Hidden, &session.account_id)
+            .await?,
+    ))
+}
+
+pub async fn remove_question(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Resolved>, MyError> {
+    session.require(Role::Moderator)?;
+    Ok(Json(
+        store
+            .resolve_question(id, Resolution::Deleted, &session.account_id)
+            .await?,
+    ))
+}
+
+pub async fn approve_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Resolved>, MyError> {
+    session.require(Role::Moderator)?;
+    Ok(Json(
+        store
+            .resolve_answer(id, Resolution::Approved, &session.account_id)
+            .await?,
+    ))
+}
+
+pub async fn hide_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Resolved>, MyError> {
+    session.require(Role::Moderator)?;
+    Ok(Json(
+        store
+            .resolve_answer(id, Resolution::Hidden, &session.account_id)
+            .await?,
+    ))
+}
+
+pub async fn remove_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Resolved>, MyError> {
+    session.require(Role::Moderator)?;
+    Ok(Json(
+        store
+            .resolve_answer(id, Resolution::Deleted, &session.account_id)
+            .await?,
+    ))
+}
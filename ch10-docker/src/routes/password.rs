@@ -0,0 +1,91 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::Utc;
+use std::result::Result::Ok;
+use std::sync::Arc;
+
+use crate::config::{Config, PasswordResetConfig};
+use crate::mailer::{Mail, Mailer};
+use crate::rate_limit::RateLimiter;
+use crate::routes::authentication::{check_password_policy, hash_password, hash_token, new_token};
+use crate::store::Store;
+use crate::types::account::{normalize_email, validate_email, PasswordForgot, PasswordReset};
+use crate::types::error::MyError;
+
+// Mails a single use reset token to the account
+// The answer is 202 whether the email is registered or not
+pub async fn forgot_password(
+    State(store): State<Store>,
+    State(config): State<Arc<Config>>,
+    State(limiter): State<RateLimiter>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    Json(request): Json<PasswordForgot>,
+) -> Result<StatusCode, MyError> {
+    let email = normalize_email(&request.email);
+    validate_email(&email)?;
+    limiter.check_email("password-forgot", &email).await?;
+
+    let account = match store.clone().get_account(email.clone()).await {
+        Ok(account) => account,
+        Err(MyError::AccountNotFound) => return Ok(StatusCode::ACCEPTED),
+        Err(e) => return Err(e),
+    };
+
+    let (token, token_hash) = new_token();
+    let expires_on = (Utc::now() + config.password_reset.lifetime).naive_utc();
+    store
+        .add_password_reset(&account.id.expect("id not found"), &token_hash, expires_on)
+        .await?;
+
+    // Sent in the background, waiting for the mail server would tell registered emails apart
+    let mail = reset_mail(&config.password_reset, email, &token);
+    tokio::spawn(async move {
+        if let Err(e) = mailer.send(&mail).await {
+            tracing::error!("could not send password reset mail: {:?}", e);
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+// Sets a new password with the token from the reset mail
+// The token is used up and every login of the account is logged out
+pub async fn reset_password(
+    State(store): State<Store>,
+    State(config): State<Arc<Config>>,
+    Json(request): Json<PasswordReset>,
+) -> Result<StatusCode, MyError> {
+    check_password_policy(&config.password_policy, &request.new_password)?;
+
+    store
+        .reset_password(
+            &hash_token(&request.token),
+            hash_password(request.new_password.as_bytes()),
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn reset_mail(config: &PasswordResetConfig, to: String, token: &str) -> Mail {
+    let separator = if config.url.contains('?') { '&' } else { '?' };
+    let body = format!(
+        "Someone asked to reset the password of your account.\n\n\
+         Choose a new password at\n{}{}token={}\n\n\
+         or send this token to /password/reset:\n{}\n\n\
+         The token works once and expires in {} minutes.\n\
+         If you did not ask for this, ignore this mail and your password stays the same.\n",
+        config.url,
+        separator,
+        token,
+        token,
+        config.lifetime.num_minutes()
+    );
+
+    Mail {
+        to,
+        subject: "Reset your password".to_string(),
+        body,
+    }
+}
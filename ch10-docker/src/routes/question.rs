@@ -1,15 +1,17 @@
 use axum::body::Body;
 use axum::extract::Path;
 
+use crate::types::listing::QuestionQuery;
 use crate::types::pagination::extract_pagination;
 use crate::types::pagination::Pagination;
+use crate::types::pagination::{Page, Paging};
 use crate::types::questions::NewQuestion;
 use axum::response::Html;
 
 use axum::Json;
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
 use std::result::Result::Ok;
@@ -18,185 +20,416 @@ use tracing::{event, Level};
 extern crate serde_json;
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
+use crate::config::Config;
+use crate::etag::{conditional_json, tagged_json, IfMatch};
+use crate::html::escape;
+use crate::moderation::Moderation;
+use crate::routes::created;
 use crate::store::Store;
-use crate::types::pagination::MyError;
-use crate::types::questions::Question;
+use crate::types::account::{Role, Session};
+use crate::types::error::{ErrorBody, MyError};
+use crate::types::questions::{Question, QuestionResponse, QuestionWithAnswers};
+use crate::types::search::SearchResults;
 
 /// Handles when router find nothing
 pub async fn handler_fallback() -> Response {
-    (StatusCode::NOT_FOUND, "404 Not Found").into_response()
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorBody {
+            code: "route_not_found",
+            message: "404 Not Found".to_string(),
+        }),
+    )
+        .into_response()
 }
 
+pub async fn get_questions_frontend(State(store): State<Store>) -> Result<Response<Body>, MyError> {
+    let mut question_result = store.get_questions_frontend().await?;
+
+    // Fill in the top answer, the frontend only shows one
+    if let Some(answer) = store
+        .get_answers_for_question(question_result.id, false)
+        .await?
+        .into_iter()
+        .next()
+    {
+        question_result.answer = answer.content;
+    }
 
-pub async fn get_questions_frontend(
-    State(store): State<Store>,
-) -> Result<Response<Body>, MyError> {
-    let question_result = store.get_questions_frontend().await?;
-    
     let response_body = match serde_json::to_string_pretty(&question_result) {
         Ok(body) => body,
-        Err(err) => return Err(MyError::DatabaseQueryError),
+        Err(_err) => return Err(MyError::SerializationError),
     };
 
     let response = Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "application/json")
         .body(Body::from(response_body))
-        .map_err(|_| MyError::DatabaseQueryError)?;
+        .map_err(|_| MyError::SerializationError)?;
 
     Ok(response)
 }
 
+// Returns one question with all of its answers, tagged with an ETag
+// Moderators also see hidden questions and answers, If-None-Match gives 304 when nothing changed
+pub async fn get_question(
+    session: Option<Session>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    State(store): State<Store>,
+) -> Result<Response, MyError> {
+    let include_hidden = sees_hidden(&session);
+    let question = store.get_question(id, include_hidden).await?;
+    let score = store.question_score(id).await?;
+    let answers = store.get_answers_for_question(id, include_hidden).await?;
+
+    let version = question.version;
+    conditional_json(
+        &headers,
+        version,
+        &QuestionWithAnswers::new(question, score, answers),
+    )
+}
+
+// Same lookup in the shape the Yew frontend expects, with the top answer inlined
+pub async fn get_question_frontend(
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    State(store): State<Store>,
+) -> Result<Response, MyError> {
+    let question = store.get_question(id, false).await?;
+    let answers = store.get_answers_for_question(id, false).await?;
+
+    conditional_json(
+        &headers,
+        question.version,
+        &QuestionResponse {
+            id: question.id.0,
+            title: question.title,
+            content: question.content,
+            answer: answers
+                .into_iter()
+                .next()
+                .map(|answer| answer.content)
+                .unwrap_or_default(),
+            tags: question.tags.map(|tags| tags.into_iter().collect()),
+            source: "".to_string(),
+        },
+    )
+}
+
+// Hidden posts stay visible to moderators and admins, the session is optional on listings
+pub fn sees_hidden(session: &Option<Session>) -> bool {
+    session
+        .as_ref()
+        .is_some_and(|session| session.has_role(Role::Moderator))
+}
+
+// Reads limit/offset from the query string, no parameters means no pagination
+fn pagination_from(params: HashMap<String, String>) -> Result<Pagination, MyError> {
+    if params.is_empty() {
+        info!(pagination = false);
+        log::info!("No pagination used");
+        return Ok(Pagination::default());
+    }
+
+    let pagination = extract_pagination(params)?;
+    log::info!("Pagination set {:?}", &pagination);
+    event!(Level::INFO, pagination = true);
+    Ok(pagination)
+}
+
+// A question listing in the shape the client asked for
+// Requests with an offset get the plain array they got before cursors existed
+enum Listing {
+    Page(Page<QuestionWithAnswers>),
+    Plain(Vec<QuestionWithAnswers>),
+}
+
+// Loads a page of questions and every answer belonging to them
+async fn list_questions(
+    store: &Store,
+    query: &QuestionQuery,
+    include_hidden: bool,
+) -> Result<Listing, MyError> {
+    match query.paging()? {
+        Paging::Cursor(page) => {
+            let Page {
+                items,
+                next_cursor,
+                total,
+            } = store
+                .get_questions_page(&page, &query.filter, query.sort, include_hidden)
+                .await?;
+            Ok(Listing::Page(Page {
+                items: questions_with_answers(store, items, include_hidden).await?,
+                next_cursor,
+                total,
+            }))
+        }
+        Paging::Offset(pagination) => {
+            let questions = store
+                .get_questions(
+                    pagination.limit,
+                    pagination.offset,
+                    &query.filter,
+                    query.sort,
+                    include_hidden,
+                )
+                .await?;
+            Ok(Listing::Plain(
+                questions_with_answers(store, questions, include_hidden).await?,
+            ))
+        }
+    }
+}
+
+// Attaches every answer and the score to each question
+// Answers are fetched in one batch so paging only ever applies to the questions
+async fn questions_with_answers(
+    store: &Store,
+    questions: Vec<Question>,
+    include_hidden: bool,
+) -> Result<Vec<QuestionWithAnswers>, MyError> {
+    let ids: Vec<i32> = questions.iter().map(|question| question.id.0).collect();
+    let mut answers = store
+        .get_answers_for_questions(&ids, include_hidden)
+        .await?;
+    let scores = store.get_question_scores(&ids).await?;
+
+    Ok(questions
+        .into_iter()
+        .map(|question| {
+            let question_answers = answers.remove(&question.id.0).unwrap_or_default();
+            let score = scores.get(&question.id.0).copied().unwrap_or(0);
+            QuestionWithAnswers::new(question, score, question_answers)
+        })
+        .collect())
+}
 
+// Full text search, e.g. /questions/search?q=largest+ocean&limit=10&offset=0
+// Paging works the same way as on the question listing
+pub async fn search_questions(
+    Query(mut params): Query<HashMap<String, String>>,
+    State(store): State<Store>,
+) -> Result<Json<SearchResults>, MyError> {
+    let query = params.remove("q").ok_or(MyError::MissingParameters)?;
+    let query = query.trim().to_string();
+    if query.is_empty() {
+        return Err(MyError::ValidationError(
+            "search query must not be empty".to_string(),
+        ));
+    }
 
+    let pagination = pagination_from(params)?;
+    let (items, total) = store
+        .search_questions(&query, pagination.limit, pagination.offset)
+        .await?;
+
+    Ok(Json(SearchResults {
+        query,
+        total,
+        items,
+    }))
+}
+
+// JSON version of the question listing, every question carries all of its answers
+// Returns {items, next_cursor, total}, or a plain array when paged with an offset
+pub async fn get_questions_json(
+    session: Option<Session>,
+    Query(params): Query<Vec<(String, String)>>,
+    State(store): State<Store>,
+) -> Result<Response, MyError> {
+    let query = QuestionQuery::parse(params)?;
+    Ok(
+        match list_questions(&store, &query, sees_hidden(&session)).await? {
+            Listing::Page(page) => Json(page).into_response(),
+            Listing::Plain(questions) => Json(questions).into_response(),
+        },
+    )
+}
 
 // Handler to get questions
 // Also handles the base line request and returns entire question json i.e. (http://localhost:3000/questions)
 pub async fn get_questions(
-    Query(params): Query<HashMap<String, String>>,
+    session: Option<Session>,
+    Query(params): Query<Vec<(String, String)>>,
     State(store): State<Store>,
 ) -> Result<impl IntoResponse, MyError> {
-    let mut pagination = Pagination::default();
-
-    // Return a set amount of questions based upon query parameters in request
-    if !params.is_empty() {
-        log::info!("Pagination set {:?}", &pagination);
-        event!(Level::INFO, pagination = true);
-        pagination = extract_pagination(params)?;
-    }
-
-    info!(pagination = false);
-    log::info!("No pagination used");
-
-    let questions_result = store
-        .get_questions(pagination.limit, pagination.offset)
-        .await;
-    let answers_result = store.get_answers(pagination.limit, pagination.offset).await;
+    let query = QuestionQuery::parse(params.clone())?;
+    let (questions, next_cursor) =
+        match list_questions(&store, &query, sees_hidden(&session)).await? {
+            Listing::Page(page) => (page.items, page.next_cursor),
+            Listing::Plain(questions) => (questions, None),
+        };
 
     // Displays questions using HTML and Javascript
     // Two buttons are created, one for a random question and one for showing all
-    match (questions_result, answers_result) {
-        (Ok(questions), Ok(answers)) => {
-            let mut html_string = String::from("<html><head><title>Questions and Answers</title></head><body><h1>Questions and Answers</h1>");
-
-            html_string.push_str(
-                r#"
-        <script>
-        function showRandomQuestion() {
-            var questions = document.querySelectorAll('.question');
-            questions.forEach(q => q.style.display = 'none');
-            var randomIndex = Math.floor(Math.random() * questions.length);
-            questions[randomIndex].style.display = 'block';
-        }
+    let mut html_string = String::from("<html><head><title>Questions and Answers</title></head><body><h1>Questions and Answers</h1>");
+
+    html_string.push_str(
+        r#"
+<script>
+function showRandomQuestion() {
+    var questions = document.querySelectorAll('.question');
+    questions.forEach(q => q.style.display = 'none');
+    var randomIndex = Math.floor(Math.random() * questions.length);
+    questions[randomIndex].style.display = 'block';
+}
 
-        function showAllQuestions() {
-            var questions = document.querySelectorAll('.question');
-            questions.forEach(q => q.style.display = 'block');
-        }
-        </script>
+function showAllQuestions() {
+    var questions = document.querySelectorAll('.question');
+    questions.forEach(q => q.style.display = 'block');
+}
+</script>
     "#,
-            );
+    );
 
-            html_string.push_str(
-                r#"
-        <button onclick="showRandomQuestion()">Random</button>
-        <button onclick="showAllQuestions()">Show All</button>
-        <ul>
+    html_string.push_str(
+        r#"
+<button onclick="showRandomQuestion()">Random</button>
+<button onclick="showAllQuestions()">Show All</button>
+<ul>
     "#,
-            );
-
-            for question in &questions {
-                let tags_str = match &question.tags {
-                    Some(tags) => tags.join(", "),
-                    None => String::from("No tags"),
-                };
-
-                html_string.push_str(&format!(
-            "<li class='question'><h2>{}</h2><p>{}</p><p>Question ID: {}</p><p>Tags: {}</p>",
-            question.title, question.content, question.id.0, tags_str,
+    );
+
+    for entry in &questions {
+        let question = &entry.question;
+        let tags_str = match &question.tags {
+            Some(tags) => escape(&tags.join(", ")),
+            None => String::from("No tags"),
+        };
+
+        // Posts are written by users, everything they wrote is escaped
+        html_string.push_str(&format!(
+            "<li class='question'><h2>{}</h2><p>{}</p><p>Question ID: {}</p><p>Tags: {}</p><p>Score: {}</p><p>Answers: {}</p>",
+            escape(&question.title), escape(&question.content), question.id.0, tags_str, entry.score, entry.answer_count,
         ));
 
-                html_string.push_str("<ul>");
-
-                // Search for the corresponding answer for the current question
-                let mut answer_found = false;
-                for answer in &answers {
-                    if answer.question_id == question.id {
-                        html_string.push_str(&format!(
-                            "<li class='answer'>Answer: {}</li>",
-                            answer.content
-                        ));
-                        answer_found = true;
-                        // We found the corresponding answer, so break out of the loop
-                        break;
-                    }
-                }
-
-                // If no corresponding answer found, display "No answer provided"
-                if !answer_found {
-                    html_string.push_str("<li class='answer'>No answer provided</li>");
-                }
-
-                html_string.push_str("</ul></li>");
-            }
-
-            html_string.push_str("</ul></body></html>");
-
-            Ok(Html(html_string))
+        html_string.push_str("<ul>");
+
+        // List every answer, accepted and best voted first
+        for answer in &entry.answers {
+            let accepted = if answer.accepted { " (accepted)" } else { "" };
+            html_string.push_str(&format!(
+                "<li class='answer'>Answer{}: {} <small>[score {}]</small></li>",
+                accepted,
+                escape(&answer.content),
+                answer.score
+            ));
         }
-        _ => Err(MyError::MissingParameters),
+
+        if entry.answers.is_empty() {
+            html_string.push_str("<li class='answer'>No answer provided</li>");
+        }
+
+        html_string.push_str("</ul></li>");
     }
+
+    html_string.push_str("</ul>");
+
+    // Link to the next page, keeping the sort, filters and page size
+    if let Some(cursor) = next_cursor {
+        let mut query: Vec<(String, String)> = params
+            .into_iter()
+            .filter(|(key, _)| key != "cursor")
+            .collect();
+        query.push(("cursor".to_string(), cursor));
+        let query = serde_urlencoded::to_string(&query).map_err(|_| MyError::SerializationError)?;
+        html_string.push_str(&format!(
+            "<a href='/questions?{}'>Next page</a>",
+            escape(&query)
+        ));
+    }
+
+    html_string.push_str("</body></html>");
+
+    Ok(Html(html_string))
 }
 
-// POST question
+// POST question, requires a logged in account
+// Title and content go through moderation first
 pub async fn add_question(
+    session: Session,
     State(store): State<Store>,
+    State(moderation): State<Moderation>,
     Json(new_question): Json<NewQuestion>,
 ) -> Result<Response, MyError> {
+    let mut new_question = new_question.validated()?;
     log::info!("ADD");
     event!(target: "practical_rust_book", Level::INFO, "ADD");
-    if let Err(_e) = store.add_question(new_question).await {
-        return Err(MyError::DatabaseQueryError);
-    }
-    let response = Response::builder()
-        .status(StatusCode::OK)
-        .body(Body::from("Question Added"))
-        .unwrap();
-
-    Ok(response)
+    let title = moderation.review(new_question.title).await?;
+    let content = moderation.review(new_question.content).await?;
+    new_question.title = title.text;
+    new_question.content = content.text;
+
+    let question = store
+        .add_question(
+            new_question,
+            session.account_id,
+            title.flagged || content.flagged,
+        )
+        .await?;
+
+    Ok(created(format!("/questions/{}", question.id.0), question))
 }
 
 // Updates question, PUT implemenation
+// Only the account that created the question may change it, the new text is moderated again
 pub async fn update_question(
+    session: Session,
     Path(id): Path<i32>,
+    headers: HeaderMap,
     State(store): State<Store>,
+    State(moderation): State<Moderation>,
     Json(question): Json<Question>,
-) -> Result<Response<Body>, MyError> {
-    let res = match store.update_question(question, id).await {
-        Ok(res) => res,
-        Err(_e) => return Err(MyError::DatabaseQueryError),
-    };
-
-    let response = Response::builder()
-        .status(StatusCode::OK)
-        .body(Body::from(serde_json::to_string_pretty(&res).unwrap()))
-        .unwrap();
-
-    Ok(response)
+) -> Result<Response, MyError> {
+    if !store.is_question_owner(id, &session.account_id).await? {
+        return Err(MyError::Forbidden);
+    }
+    let expected = IfMatch::from_headers(&headers)?;
+    let mut question = question.validated()?;
+
+    let title = moderation.review(question.title).await?;
+    let content = moderation.review(question.content).await?;
+    question.title = title.text;
+    question.content = content.text;
+
+    let res = store
+        .update_question(
+            question,
+            id,
+            title.flagged || content.flagged,
+            &session.account_id,
+            &expected,
+        )
+        .await?;
+
+    tagged_json(res.version, &res)
 }
 
-// Deletes question and correlated answer if exists, DELETE implemenation
+// Deletes a question with its answers, its author and moderators may do this
+// With soft_delete on the question is only marked and can be restored
 pub async fn delete_question(
+    session: Session,
     Path(id): Path<i32>,
+    headers: HeaderMap,
     State(store): State<Store>,
+    State(config): State<Arc<Config>>,
 ) -> Result<Response, MyError> {
-    if let Err(_e) = store.delete_answer(id).await {
-        return Err(MyError::DatabaseQueryError);
+    if !session.has_role(Role::Moderator)
+        && !store.is_question_owner(id, &session.account_id).await?
+    {
+        return Err(MyError::Forbidden);
     }
+    let expected = IfMatch::from_headers(&headers)?;
 
-    if let Err(_e) = store.delete_question(id).await {
-        return Err(MyError::DatabaseQueryError);
+    if config.soft_delete {
+        store.soft_delete_question(id, &expected).await?;
+    } else {
+        store.delete_question(id, &expected).await?;
     }
 
     let response = Response::builder()
@@ -206,3 +439,24 @@ pub async fn delete_question(
 
     Ok(response)
 }
+
+// Brings back a soft deleted question, its author and moderators may do this
+// If-Match carries the ETag the question had when it was deleted
+pub async fn restore_question(
+    session: Session,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    State(store): State<Store>,
+) -> Result<Response, MyError> {
+    if !session.has_role(Role::Moderator)
+        && !store
+            .is_deleted_question_owner(id, &session.account_id)
+            .await?
+    {
+        return Err(MyError::Forbidden);
+    }
+    let expected = IfMatch::from_headers(&headers)?;
+
+    let question = store.restore_question(id, &expected).await?;
+    tagged_json(question.version, &question)
+}
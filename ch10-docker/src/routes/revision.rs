@@ -0,0 +1,124 @@
+use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
+use axum::response::Response;
+use axum::Json;
+use std::result::Result::Ok;
+
+use crate::etag::{tagged_json, IfMatch};
+use crate::moderation::Moderation;
+use crate::routes::answer::visible_answer;
+use crate::routes::question::sees_hidden;
+use crate::store::Store;
+use crate::types::account::{Role, Session};
+use crate::types::answer::Answer;
+use crate::types::error::MyError;
+use crate::types::questions::NewQuestion;
+use crate::types::revision::{AnswerRevision, DiffQuery, QuestionRevision, RevisionDiff};
+
+// Every saved version of a question, oldest first, for anyone who can see the question
+pub async fn get_question_revisions(
+    session: Option<Session>,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Vec<QuestionRevision>>, MyError> {
+    store.get_question(id, sees_hidden(&session)).await?;
+    Ok(Json(store.get_question_revisions(id).await?))
+}
+
+// What changed between two revisions of a question, e.g. ?from=1&to=3
+pub async fn diff_question_revisions(
+    session: Option<Session>,
+    Path(id): Path<i32>,
+    Query(range): Query<DiffQuery>,
+    State(store): State<Store>,
+) -> Result<Json<RevisionDiff>, MyError> {
+    store.get_question(id, sees_hidden(&session)).await?;
+    let from = store.get_question_revision(id, range.from).await?;
+    let to = store.get_question_revision(id, range.to).await?;
+    Ok(Json(from.diff(&to)))
+}
+
+// Puts an earlier revision of a question back, its author and moderators may do this
+// Needs If-Match like an edit, so a rollback cannot overwrite a change it has not seen
+// The old text is moderated again like an edit, it may predate the current settings
+pub async fn rollback_question(
+    session: Session,
+    Path((id, revision)): Path<(i32, i32)>,
+    headers: HeaderMap,
+    State(store): State<Store>,
+    State(moderation): State<Moderation>,
+) -> Result<Response, MyError> {
+    if !session.has_role(Role::Moderator)
+        && !store.is_question_owner(id, &session.account_id).await?
+    {
+        return Err(MyError::Forbidden);
+    }
+    let expected = IfMatch::from_headers(&headers)?;
+
+    let earlier = store.get_question_revision(id, revision).await?;
+
+    let title = moderation.review(earlier.title).await?;
+    let content = moderation.review(earlier.content).await?;
+    let text = NewQuestion {
+        title: title.text,
+        content: content.text,
+        tags: earlier.tags,
+    };
+
+    let question = store
+        .revise_question(
+            id,
+            text,
+            title.flagged || content.flagged,
+            &session.account_id,
+            &expected,
+        )
+        .await?;
+    tagged_json(question.version, &question)
+}
+
+// Every saved version of an answer, oldest first, for anyone who can see the answer
+pub async fn get_answer_revisions(
+    session: Option<Session>,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<Vec<AnswerRevision>>, MyError> {
+    visible_answer(&store, id, sees_hidden(&session)).await?;
+    Ok(Json(store.get_answer_revisions(id).await?))
+}
+
+// What changed between two revisions of an answer, e.g. ?from=1&to=3
+pub async fn diff_answer_revisions(
+    session: Option<Session>,
+    Path(id): Path<i32>,
+    Query(range): Query<DiffQuery>,
+    State(store): State<Store>,
+) -> Result<Json<RevisionDiff>, MyError> {
+    visible_answer(&store, id, sees_hidden(&session)).await?;
+    let from = store.get_answer_revision(id, range.from).await?;
+    let to = store.get_answer_revision(id, range.to).await?;
+    Ok(Json(from.diff(&to)))
+}
+
+// Puts an earlier revision of an answer back, its author and moderators may do this
+// The old text is moderated again like an edit
+pub async fn rollback_answer(
+    session: Session,
+    Path((id, revision)): Path<(i32, i32)>,
+    State(store): State<Store>,
+    State(moderation): State<Moderation>,
+) -> Result<Json<Answer>, MyError> {
+    if !session.has_role(Role::Moderator) && !store.is_answer_owner(id, &session.account_id).await?
+    {
+        return Err(MyError::Forbidden);
+    }
+
+    let earlier = store.get_answer_revision(id, revision).await?;
+
+    let content = moderation.review(earlier.content).await?;
+    Ok(Json(
+        store
+            .update_answer(id, content.text, content.flagged, &session.account_id)
+            .await?,
+    ))
+}
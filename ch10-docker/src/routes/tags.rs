@@ -0,0 +1,12 @@
+use axum::extract::State;
+use axum::Json;
+use std::result::Result::Ok;
+
+use crate::store::Store;
+use crate::types::error::MyError;
+use crate::types::tags::TagCount;
+
+// Lists every tag in use with the number of questions carrying it
+pub async fn get_tags(State(store): State<Store>) -> Result<Json<Vec<TagCount>>, MyError> {
+    Ok(Json(store.get_tags().await?))
+}
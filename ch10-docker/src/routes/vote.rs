@@ -0,0 +1,64 @@
+use axum::extract::Path;
+use axum::extract::State;
+use axum::Json;
+use std::result::Result::Ok;
+
+use crate::routes::answer::visible_answer;
+use crate::store::Store;
+use crate::types::account::Session;
+use crate::types::error::MyError;
+use crate::types::vote::{Vote, VoteSummary};
+
+// Up or down vote a question, one vote per account
+// Hidden and deleted questions count as missing
+pub async fn vote_question(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+    Json(vote): Json<Vote>,
+) -> Result<Json<VoteSummary>, MyError> {
+    let value = vote.validated()?;
+    store.get_question(id, false).await?;
+    let score = store.vote_question(id, &session.account_id, value).await?;
+
+    Ok(Json(VoteSummary { score }))
+}
+
+// Takes back the account's vote on a question
+pub async fn unvote_question(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<VoteSummary>, MyError> {
+    store.get_question(id, false).await?;
+    let score = store.remove_question_vote(id, &session.account_id).await?;
+
+    Ok(Json(VoteSummary { score }))
+}
+
+// Up or down vote an answer, one vote per account
+// Hidden answers and answers to hidden or deleted questions count as missing
+pub async fn vote_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+    Json(vote): Json<Vote>,
+) -> Result<Json<VoteSummary>, MyError> {
+    let value = vote.validated()?;
+    visible_answer(&store, id, false).await?;
+    let score = store.vote_answer(id, &session.account_id, value).await?;
+
+    Ok(Json(VoteSummary { score }))
+}
+
+// Takes back the account's vote on an answer
+pub async fn unvote_answer(
+    session: Session,
+    Path(id): Path<i32>,
+    State(store): State<Store>,
+) -> Result<Json<VoteSummary>, MyError> {
+    visible_answer(&store, id, false).await?;
+    let score = store.remove_answer_vote(id, &session.account_id).await?;
+
+    Ok(Json(VoteSummary { score }))
+}
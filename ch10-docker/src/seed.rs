@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::store::Store;
+use crate::types::error::MyError;
+use crate::types::questions::Question;
+use crate::types::tags::normalize_tags;
+
+// Questions shipped with the binary, used when no seed file is given
+const DEFAULT_SEED: &str = include_str!("questions.json");
+
+// Seed files are either an object keyed by id, like questions.json, or a plain array
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SeedFile {
+    Keyed(HashMap<String, Question>),
+    List(Vec<Question>),
+}
+
+// What a seeding run did
+#[derive(Serialize, Debug, Default)]
+pub struct SeedReport {
+    pub inserted: Vec<i32>,
+    pub skipped: Vec<i32>,
+}
+
+// Parses seed questions, sorted by id
+pub fn parse(json: &str) -> Result<Vec<Question>, MyError> {
+    let mut questions = match serde_json::from_str::<SeedFile>(json) {
+        Ok(SeedFile::Keyed(questions)) => questions.into_values().collect::<Vec<Question>>(),
+        Ok(SeedFile::List(questions)) => questions,
+        Err(e) => {
+            return Err(MyError::ValidationError(format!(
+                "seed data is not valid: {}",
+                e
+            )))
+        }
+    };
+    questions.sort_by_key(|question| question.id.0);
+    Ok(questions)
+}
+
+// Reads seed questions from a file, or the built in set when no file is given
+pub fn load(file: Option<&Path>) -> Result<Vec<Question>, MyError> {
+    match file {
+        Some(path) => {
+            let json = std::fs::read_to_string(path).map_err(|e| {
+                MyError::ValidationError(format!("could not read {}: {}", path.display(), e))
+            })?;
+            parse(&json)
+        }
+        None => parse(DEFAULT_SEED),
+    }
+}
+
+// Inserts the questions in one transaction, keeping their ids
+// Questions whose id already exists are left untouched, so running it twice is harmless
+pub async fn seed(store: &Store, questions: &[Question]) -> Result<SeedReport, MyError> {
+    let mut report = SeedReport::default();
+    let mut tx = store.connection.begin().await?;
+
+    for question in questions {
+        let inserted = sqlx::query(
+            "INSERT INTO questions (id, title, content, tags) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (id) DO NOTHING
+                RETURNING id",
+        )
+        .bind(question.id.0)
+        .bind(&question.title)
+        .bind(&question.content)
+        .bind(normalize_tags(question.tags.clone()))
+        .map(|row: PgRow| row.get::<i32, _>("id"))
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        match inserted {
+            Some(id) => report.inserted.push(id),
+            None => report.skipped.push(question.id.0),
+        }
+    }
+
+    fix_sequences(&mut tx).await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "seeded {} questions, {} already present",
+        report.inserted.len(),
+        report.skipped.len()
+    );
+    Ok(report)
+}
+
+// Seeds the built in questions only when the questions table is empty
+pub async fn seed_if_empty(store: &Store) -> Result<Option<SeedReport>, MyError> {
+    let count: i64 = sqlx::query("SELECT COUNT(*) AS count FROM questions")
+        .map(|row: PgRow| row.get("count"))
+        .fetch_one(&store.connection)
+        .await?;
+
+    if count > 0 {
+        return Ok(None);
+    }
+    Ok(Some(seed(store, &load(None)?).await?))
+}
+
+// Moves the id sequences past the highest id in use
+// Rows inserted with explicit ids never advance the sequence on their own
+pub async fn fix_sequences(connection: &mut sqlx::PgConnection) -> Result<(), MyError> {
+    for table in ["questions", "answers"] {
+        sqlx::query(&format!(
+            "SELECT setval(pg_get_serial_sequence('{table}', 'id'),
+                COALESCE((SELECT MAX(id) FROM {table}), 0) + 1, false)"
+        ))
+        .execute(&mut *connection)
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use crate::config::Config;
+use crate::mailer::Mailer;
+use crate::moderation::Moderation;
+use crate::rate_limit::RateLimiter;
+use crate::store::Store;
+use axum::extract::FromRef;
+use std::sync::Arc;
+
+// Shared state handed to every handler
+// Handlers still extract State<Store>, State<Arc<Config>>, State<Moderation>,
+// State<RateLimiter> or State<Arc<dyn Mailer>> directly
+#[derive(Clone)]
+pub struct AppState {
+    pub store: Store,
+    pub config: Arc<Config>,
+    pub moderation: Moderation,
+    pub rate_limiter: RateLimiter,
+    pub mailer: Arc<dyn Mailer>,
+}
+
+impl FromRef<AppState> for Store {
+    fn from_ref(state: &AppState) -> Store {
+        state.store.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Arc<Config> {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for Moderation {
+    fn from_ref(state: &AppState) -> Moderation {
+        state.moderation.clone()
+    }
+}
+
+impl FromRef<AppState> for RateLimiter {
+    fn from_ref(state: &AppState) -> RateLimiter {
+        state.rate_limiter.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Mailer> {
+    fn from_ref(state: &AppState) -> Arc<dyn Mailer> {
+        state.mailer.clone()
+    }
+}
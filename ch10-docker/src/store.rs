@@ -1,18 +1,47 @@
-use crate::types::account::AccountId;
-use crate::types::pagination::MyError;
+use crate::config::DatabaseConfig;
+use crate::etag::IfMatch;
+use crate::types::account::{AccountId, AccountProfile, Role};
+use crate::types::error::MyError;
 use crate::types::questions::NewQuestion;
 use crate::types::{
     account::Account,
     answer::{Answer, AnswerId, NewAnswer},
+    listing::{QuestionFilter, QuestionSort},
+    pagination::{Cursor, CursorPage, Page},
     questions::{Question, QuestionId, QuestionResponse},
+    report::{PostKind, Report, Resolution, Resolved, ReviewItem},
+    revision::{AnswerRevision, QuestionRevision},
+    search::{highlight, SearchHit, HEADLINE_OPTIONS, MATCH_END, MATCH_START},
+    tags::{normalize_tags, TagCount, TagMatch},
+    transfer::{AnswerRecord, QuestionRecord},
 };
-use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+use chrono::NaiveDateTime;
+use sqlx::postgres::{PgConnectOptions, PgConnection, PgPool, PgPoolOptions, PgRow};
 use sqlx::Row;
 use std::collections::HashMap;
-use std::result::Result::Ok;
-use axum::http::{Response, StatusCode};
-use axum::body::Body;
 use std::collections::HashSet;
+use std::result::Result::Ok;
+
+// Columns selected for every answer, the score is the sum of its votes
+const ANSWER_COLUMNS: &str = "id, content, corresponding_question, created_on, accepted,
+    (SELECT COALESCE(SUM(value), 0) FROM answer_votes WHERE answer_id = answers.id) AS score";
+
+// Accepted answer first, then the best voted, then the oldest
+const ANSWER_ORDER: &str = "accepted DESC, score DESC, created_on, id";
+
+// Conditions shared by the question listings, bound by `bind_question_filter`
+// $1 tags, $2 every tag has to match, $3 hidden posts included, $4 unanswered only,
+// $5 author, $6 created on or after, $7 created before
+// Soft deleted questions are left out even for moderators
+const QUESTION_FILTER: &str = "questions.deleted_at IS NULL
+    AND ($3 OR NOT questions.hidden)
+    AND (cardinality($1::text[]) = 0
+        OR (CASE WHEN $2 THEN questions.tags @> $1 ELSE questions.tags && $1 END))
+    AND (NOT $4 OR NOT EXISTS (SELECT 1 FROM answers
+        WHERE answers.corresponding_question = questions.id AND ($3 OR NOT answers.hidden)))
+    AND ($5::integer IS NULL OR questions.account_id = $5)
+    AND ($6::timestamp IS NULL OR questions.created_on >= $6)
+    AND ($7::timestamp IS NULL OR questions.created_on < $7)";
 
 #[derive(Clone)]
 pub struct Store {
@@ -22,9 +51,18 @@ pub struct Store {
 #[allow(dead_code)]
 // Connect PostgreSQL database to store class
 impl Store {
-    pub async fn new() -> Self {
-        let url: &str = "postgres://postgres:1234@db:5432/questions";
-        let db_pool = match PgPoolOptions::new().max_connections(5).connect(url).await {
+    pub async fn new(config: &DatabaseConfig) -> Self {
+        let options = PgConnectOptions::new()
+            .host(&config.host)
+            .port(config.port)
+            .database(&config.name)
+            .username(&config.user)
+            .password(&config.password);
+        let db_pool = match PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect_with(options)
+            .await
+        {
             Ok(pool) => pool,
             Err(e) => panic!("Couldn't establish DB connection: {}", e),
         };
@@ -41,178 +79,678 @@ impl Store {
                 id: Some(AccountId(row.get("id"))),
                 email: row.get("email"),
                 password: row.get("password"),
+                role: row.get::<String, _>("role").parse().unwrap_or_default(),
             })
             .fetch_one(&self.connection)
             .await
         {
             Ok(account) => Ok(account),
-            Err(error) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", error);
-                Err(MyError::DatabaseQueryError)
-            }
+            Err(sqlx::Error::RowNotFound) => Err(MyError::AccountNotFound),
+            Err(error) => Err(MyError::DatabaseQueryError(error)),
         }
     }
 
-    pub async fn add_account(self, account: Account) -> Result<bool, MyError> {
+    // Gives an account a new role, it applies from the next login
+    pub async fn set_account_role(
+        &self,
+        account_id: &AccountId,
+        role: Role,
+    ) -> Result<AccountProfile, MyError> {
+        sqlx::query("UPDATE accounts SET role = $2 WHERE id = $1 RETURNING id, email")
+            .bind(account_id.0)
+            .bind(role.as_str())
+            .map(|row: PgRow| AccountProfile {
+                id: AccountId(row.get("id")),
+                email: row.get("email"),
+                role,
+            })
+            .fetch_optional(&self.connection)
+            .await?
+            .ok_or(MyError::AccountNotFound)
+    }
+
+    pub async fn add_account(self, account: Account) -> Result<AccountId, MyError> {
         match sqlx::query(
             "INSERT INTO accounts (email, password)
-            VALUES ($1, $2)",
+            VALUES ($1, $2)
+            RETURNING id",
         )
         .bind(account.email)
         .bind(account.password)
-        .execute(&self.connection)
+        .map(|row: PgRow| AccountId(row.get("id")))
+        .fetch_one(&self.connection)
         .await
         {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(MyError::DatabaseQueryError)
-            }
+            Ok(account_id) => Ok(account_id),
+            Err(e) => Err(MyError::DatabaseQueryError(e)),
         }
     }
 
-    
-    
+    pub async fn get_account_by_id(&self, account_id: &AccountId) -> Result<Account, MyError> {
+        sqlx::query("SELECT * from accounts where id = $1")
+            .bind(account_id.0)
+            .map(|row: PgRow| Account {
+                id: Some(AccountId(row.get("id"))),
+                email: row.get("email"),
+                password: row.get("password"),
+                role: row.get::<String, _>("role").parse().unwrap_or_default(),
+            })
+            .fetch_optional(&self.connection)
+            .await?
+            .ok_or(MyError::AccountNotFound)
+    }
 
+    // Stores a new password hash and logs out every other login of the account
+    pub async fn update_password(
+        &self,
+        account_id: &AccountId,
+        password_hash: String,
+        keep_family: i32,
+    ) -> Result<(), MyError> {
+        let mut tx = self.connection.begin().await?;
 
-    pub async fn get_questions_frontend(&self) -> Result<QuestionResponse, MyError> {
-    // Fetch a random question from the database
-    let random_question = sqlx::query("SELECT * FROM questions ORDER BY RANDOM() LIMIT 1")
-        .map(|row: sqlx::postgres::PgRow| QuestionResponse {
-            id: row.get("id"),
-            title: row.get("title"),
-            content: row.get("content"),
-            answer: "".to_string(),
-            source: "".to_string(), // Set answer to empty string
-            tags: row.try_get::<Option<Vec<String>>, _>("tags")
-                .ok()
-                .map(|tags| tags.into_iter().flatten().collect::<HashSet<String>>()),
-        })
-        .fetch_one(&self.connection)
-        .await
-        .map_err(|_| MyError::QuestionNotFound)?;
+        let updated = sqlx::query("UPDATE accounts SET password = $2 WHERE id = $1")
+            .bind(account_id.0)
+            .bind(password_hash)
+            .execute(&mut *tx)
+            .await?;
+        if updated.rows_affected() == 0 {
+            return Err(MyError::AccountNotFound);
+        }
 
-    Ok(random_question)
-}
+        sqlx::query(
+            "UPDATE token_families SET revoked_on = NOW()
+                WHERE account_id = $1 AND id <> $2 AND revoked_on IS NULL",
+        )
+        .bind(account_id.0)
+        .bind(keep_family)
+        .execute(&mut *tx)
+        .await?;
 
+        tx.commit().await?;
+        Ok(())
+    }
 
+    // Removes the account, its logins and its password reset tokens
+    // Questions and answers stay, they just no longer belong to anyone
+    pub async fn delete_account(&self, account_id: &AccountId) -> Result<(), MyError> {
+        let mut tx = self.connection.begin().await?;
 
+        for table in ["questions", "answers"] {
+            sqlx::query(&format!(
+                "UPDATE {table} SET account_id = NULL WHERE account_id = $1"
+            ))
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await?;
+        }
+        for table in ["token_families", "password_resets"] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE account_id = $1"))
+                .bind(account_id.0)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let deleted = sqlx::query("DELETE FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(MyError::AccountNotFound);
+        }
 
+        tx.commit().await?;
+        Ok(())
+    }
 
+    // Stores a password reset token for the account
+    // Older reset tokens of the account stop working, only the newest mail is valid
+    pub async fn add_password_reset(
+        &self,
+        account_id: &AccountId,
+        token_hash: &str,
+        expires_on: NaiveDateTime,
+    ) -> Result<(), MyError> {
+        let mut tx = self.connection.begin().await?;
 
-    // Returns all questions from the database
-    pub async fn get_questions(
+        sqlx::query("DELETE FROM password_resets WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO password_resets (token_hash, account_id, expires_on) VALUES ($1, $2, $3)",
+        )
+        .bind(token_hash)
+        .bind(account_id.0)
+        .bind(expires_on)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Uses up a reset token and stores the new password hash
+    // Every login of the account is logged out, whoever had the old password loses access
+    pub async fn reset_password(
         &self,
-        limit: Option<i32>,
-        offset: i32,
-    ) -> Result<Vec<Question>, MyError> {
-        
-        // Check if questions table is empty
-        let count_query = sqlx::query("SELECT COUNT(*) as count FROM questions")
-            .fetch_one(&self.connection)
-            .await
-            .unwrap();
+        token_hash: &str,
+        password_hash: String,
+    ) -> Result<AccountId, MyError> {
+        let mut tx = self.connection.begin().await?;
 
-        let count: i64 = count_query.get("count");
+        let account_id: i32 = sqlx::query(
+            "UPDATE password_resets SET used_on = NOW()
+                WHERE token_hash = $1 AND used_on IS NULL AND expires_on > NOW()
+                RETURNING account_id",
+        )
+        .bind(token_hash)
+        .map(|row: PgRow| row.get("account_id"))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            MyError::ValidationError("reset token is invalid or has expired".to_string())
+        })?;
 
-        // If questions table is empty, seed data from questions.json
-        if count == 0 {
-            let json_str = include_str!("questions.json");
-            let json_questions: Result<HashMap<u32, Question>, _> = serde_json::from_str(json_str);
+        sqlx::query("UPDATE accounts SET password = $2 WHERE id = $1")
+            .bind(account_id)
+            .bind(password_hash)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "UPDATE token_families SET revoked_on = NOW()
+                WHERE account_id = $1 AND revoked_on IS NULL",
+        )
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await?;
 
-            let questions_to_insert = match json_questions {
-                Ok(questions) => questions,
-                Err(err) => {
-                    println!("An error occurred while parsing JSON: {:?}", err);
-                    return Err(MyError::QuestionNotFound);
-                }
-            };
+        tx.commit().await?;
+        Ok(AccountId(account_id))
+    }
 
-            let questions: Vec<Question> =
-                questions_to_insert.into_values().collect();
+    // Starts a new login for the account with its first refresh token
+    // Expired refresh tokens of the account are cleaned up on the way
+    pub async fn start_token_family(
+        &self,
+        account_id: &AccountId,
+        token_hash: &str,
+        expires_on: NaiveDateTime,
+    ) -> Result<i32, MyError> {
+        let mut tx = self.connection.begin().await?;
 
-            for question in &questions {
-                if sqlx::query(
-                    "INSERT INTO questions (id, title, content, tags) VALUES ($1, $2, $3, $4)",
-                )
-                .bind(question.id.0)
-                .bind(&question.title)
-                .bind(&question.content)
-                .bind(&question.tags)
-                .execute(&self.connection)
-                .await
-                .is_err()
-                {
-                    println!("An error occurred: &questions");
-                    return Err(MyError::DatabaseQueryError);
-                }
-            }
+        sqlx::query(
+            "DELETE FROM refresh_tokens USING token_families
+                WHERE refresh_tokens.family_id = token_families.id
+                AND token_families.account_id = $1
+                AND refresh_tokens.expires_on < NOW()",
+        )
+        .bind(account_id.0)
+        .execute(&mut *tx)
+        .await?;
 
-            Ok(questions)
-        } else {
-            // Table has entries already, we return those
-
-            // Fetch questions from the database
-            let fetched_questions = sqlx::query("SELECT * from questions LIMIT $1 OFFSET $2")
-                .bind(limit)
-                .bind(offset)
-                .map(|row: PgRow| Question {
-                    id: QuestionId(row.get("id")),
+        let family: i32 =
+            sqlx::query("INSERT INTO token_families (account_id) VALUES ($1) RETURNING id")
+                .bind(account_id.0)
+                .map(|row: PgRow| row.get("id"))
+                .fetch_one(&mut *tx)
+                .await?;
+
+        sqlx::query(
+            "INSERT INTO refresh_tokens (token_hash, family_id, expires_on) VALUES ($1, $2, $3)",
+        )
+        .bind(token_hash)
+        .bind(family)
+        .bind(expires_on)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(family)
+    }
+
+    // Swaps a refresh token for a new one in the same family
+    // A token that was already used means it leaked, so the whole family is revoked
+    pub async fn rotate_refresh_token(
+        &self,
+        token_hash: &str,
+        new_token_hash: &str,
+        expires_on: NaiveDateTime,
+    ) -> Result<(AccountId, Role, i32), MyError> {
+        let mut tx = self.connection.begin().await?;
+
+        let current = sqlx::query(
+            "SELECT refresh_tokens.family_id, refresh_tokens.used_on,
+                    refresh_tokens.expires_on < NOW() AS expired,
+                    token_families.revoked_on, accounts.id AS account_id, accounts.role
+                FROM refresh_tokens
+                JOIN token_families ON token_families.id = refresh_tokens.family_id
+                JOIN accounts ON accounts.id = token_families.account_id
+                WHERE refresh_tokens.token_hash = $1
+                FOR UPDATE OF refresh_tokens",
+        )
+        .bind(token_hash)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(MyError::Unauthorized)?;
+
+        let family: i32 = current.get("family_id");
+        if current
+            .get::<Option<NaiveDateTime>, _>("revoked_on")
+            .is_some()
+            || current.get::<bool, _>("expired")
+        {
+            return Err(MyError::Unauthorized);
+        }
+        if current.get::<Option<NaiveDateTime>, _>("used_on").is_some() {
+            tracing::warn!("refresh token reused, revoking token family {}", family);
+            sqlx::query("UPDATE token_families SET revoked_on = NOW() WHERE id = $1")
+                .bind(family)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            return Err(MyError::Unauthorized);
+        }
+
+        sqlx::query("UPDATE refresh_tokens SET used_on = NOW() WHERE token_hash = $1")
+            .bind(token_hash)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO refresh_tokens (token_hash, family_id, expires_on) VALUES ($1, $2, $3)",
+        )
+        .bind(new_token_hash)
+        .bind(family)
+        .bind(expires_on)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok((
+            AccountId(current.get("account_id")),
+            current.get::<String, _>("role").parse().unwrap_or_default(),
+            family,
+        ))
+    }
+
+    // True while the login has not been revoked
+    pub async fn is_token_family_active(&self, family: i32) -> Result<bool, MyError> {
+        Ok(sqlx::query(
+            "SELECT EXISTS (SELECT 1 FROM token_families WHERE id = $1 AND revoked_on IS NULL)
+                AS active",
+        )
+        .bind(family)
+        .map(|row: PgRow| row.get("active"))
+        .fetch_one(&self.connection)
+        .await?)
+    }
+
+    // Logs one login out, its access and refresh tokens stop working
+    pub async fn revoke_token_family(&self, family: i32) -> Result<(), MyError> {
+        sqlx::query(
+            "UPDATE token_families SET revoked_on = NOW() WHERE id = $1 AND revoked_on IS NULL",
+        )
+        .bind(family)
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+
+    // Logs every login of the account out, returns how many were still active
+    pub async fn revoke_account_tokens(&self, account_id: &AccountId) -> Result<u64, MyError> {
+        Ok(sqlx::query(
+            "UPDATE token_families SET revoked_on = NOW()
+                WHERE account_id = $1 AND revoked_on IS NULL",
+        )
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await?
+        .rows_affected())
+    }
+
+    pub async fn get_questions_frontend(&self) -> Result<QuestionResponse, MyError> {
+        // Fetch a random question from the database
+        let random_question =
+            sqlx::query("SELECT * FROM questions WHERE NOT hidden AND deleted_at IS NULL ORDER BY RANDOM() LIMIT 1")
+                .map(|row: sqlx::postgres::PgRow| QuestionResponse {
+                    id: row.get("id"),
                     title: row.get("title"),
                     content: row.get("content"),
-                    tags: row.get("tags"),
+                    answer: "".to_string(),
+                    source: "".to_string(), // Set answer to empty string
+                    tags: row
+                        .try_get::<Option<Vec<String>>, _>("tags")
+                        .ok()
+                        .map(|tags| tags.into_iter().flatten().collect::<HashSet<String>>()),
                 })
-                .fetch_all(&self.connection)
+                .fetch_one(&self.connection)
                 .await
-                .map_err(|_| {
-                    println!("An error occurred: fetched");
-                    MyError::QuestionNotFound
+                .map_err(|e| match e {
+                    sqlx::Error::RowNotFound => MyError::QuestionNotFound,
+                    e => MyError::DatabaseQueryError(e),
                 })?;
 
-            Ok(fetched_questions)
-        }
+        Ok(random_question)
     }
 
-    // Grabs all answers in database
-    pub async fn get_answers(
+    // Returns questions in the requested order using limit/offset, hidden ones only when asked for
+    pub async fn get_questions(
         &self,
         limit: Option<i32>,
         offset: i32,
-    ) -> Result<Vec<Answer>, MyError> {
-        match sqlx::query("SELECT * from answers LIMIT $1 OFFSET $2")
+        filter: &QuestionFilter,
+        sort: QuestionSort,
+        include_hidden: bool,
+    ) -> Result<Vec<Question>, MyError> {
+        let sql = format!(
+            "{} ORDER BY {} LIMIT $8 OFFSET $9",
+            question_listing(),
+            sort_order(sort)
+        );
+        let fetched_questions = bind_question_filter(sqlx::query(&sql), filter, include_hidden)
             .bind(limit)
             .bind(offset)
-            .map(|row: PgRow| Answer {
-                id: AnswerId(row.get("id")),
-                content: row.get("content"),
-                question_id: QuestionId(row.get("corresponding_question")),
+            .map(|row: PgRow| question_from_row(&row))
+            .fetch_all(&self.connection)
+            .await?;
+
+        Ok(fetched_questions)
+    }
+
+    // One page of questions in the requested order, starting after the cursor
+    pub async fn get_questions_page(
+        &self,
+        page: &CursorPage,
+        filter: &QuestionFilter,
+        sort: QuestionSort,
+        include_hidden: bool,
+    ) -> Result<Page<Question>, MyError> {
+        let (after_created_on, after_id) = cursor_bounds(page);
+        let after_key = page.after.and_then(|cursor| cursor.key);
+        // One row more than asked for tells whether another page follows
+        let sql = format!(
+            "{} AND ($8::timestamp IS NULL OR {}) ORDER BY {} LIMIT $11",
+            question_listing(),
+            sort_after(sort),
+            sort_order(sort)
+        );
+        let rows = bind_question_filter(sqlx::query(&sql), filter, include_hidden)
+            .bind(after_created_on)
+            .bind(after_id)
+            .bind(after_key)
+            .bind(page.limit + 1)
+            .map(|row: PgRow| {
+                let question = question_from_row(&row);
+                let key = match sort {
+                    QuestionSort::MostAnswers => Some(row.get("answer_count")),
+                    QuestionSort::Score => Some(row.get("score")),
+                    QuestionSort::Oldest | QuestionSort::Newest => None,
+                };
+                (question, key)
             })
             .fetch_all(&self.connection)
-            .await
+            .await?;
+
+        let total: i64 = bind_question_filter(
+            sqlx::query(&format!(
+                "SELECT COUNT(*) AS total FROM questions WHERE {}",
+                QUESTION_FILTER
+            )),
+            filter,
+            include_hidden,
+        )
+        .map(|row: PgRow| row.get("total"))
+        .fetch_one(&self.connection)
+        .await?;
+
+        let page = Page::new(rows, page.limit, total, |(question, key)| Cursor {
+            created_on: question.created_on,
+            id: question.id.0,
+            key: *key,
+        });
+        Ok(page.map(|(question, _)| question))
+    }
+
+    // Every distinct tag with the number of questions carrying it, most used first
+    pub async fn get_tags(&self) -> Result<Vec<TagCount>, MyError> {
+        Ok(sqlx::query(
+            "SELECT tag, COUNT(*) AS questions FROM questions, unnest(tags) AS tag
+                WHERE NOT hidden AND deleted_at IS NULL
+                GROUP BY tag
+                ORDER BY questions DESC, tag",
+        )
+        .map(|row: PgRow| TagCount {
+            tag: row.get("tag"),
+            questions: row.get("questions"),
+        })
+        .fetch_all(&self.connection)
+        .await?)
+    }
+
+    // Returns a single question by id, a hidden one counts as missing unless asked for
+    // A soft deleted one is always missing
+    pub async fn get_question(
+        &self,
+        question_id: i32,
+        include_hidden: bool,
+    ) -> Result<Question, MyError> {
+        match sqlx::query(
+            "SELECT id, title, content, tags, created_on, version FROM questions
+                WHERE id = $1 AND ($2 OR NOT hidden) AND deleted_at IS NULL",
+        )
+        .bind(question_id)
+        .bind(include_hidden)
+        .map(|row: PgRow| question_from_row(&row))
+        .fetch_one(&self.connection)
+        .await
         {
-            Ok(questions) => Ok(questions),
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(MyError::DatabaseQueryError)
-            }
+            Ok(question) => Ok(question),
+            Err(sqlx::Error::RowNotFound) => Err(MyError::QuestionNotFound),
+            Err(e) => Err(MyError::DatabaseQueryError(e)),
+        }
+    }
+
+    // Grabs every answer given to one question, accepted and best voted first
+    pub async fn get_answers_for_question(
+        &self,
+        question_id: i32,
+        include_hidden: bool,
+    ) -> Result<Vec<Answer>, MyError> {
+        match sqlx::query(&format!(
+            "SELECT {} FROM answers WHERE corresponding_question = $1 AND ($2 OR NOT hidden)
+                ORDER BY {}",
+            ANSWER_COLUMNS, ANSWER_ORDER
+        ))
+        .bind(question_id)
+        .bind(include_hidden)
+        .map(answer_from_row)
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(answers) => Ok(answers),
+            Err(e) => Err(MyError::DatabaseQueryError(e)),
         }
     }
 
-    // Adds a new question to the database
-    pub async fn add_question(&self, new_question: NewQuestion) -> Result<Question, sqlx::Error> {
+    // One page of the answers to a question in (created_on, id) order, starting after the cursor
+    pub async fn get_answers_page(
+        &self,
+        question_id: i32,
+        page: &CursorPage,
+        include_hidden: bool,
+    ) -> Result<Page<Answer>, MyError> {
+        let (after_created_on, after_id) = cursor_bounds(page);
+        let answers = sqlx::query(&format!(
+            "SELECT {} FROM answers
+                WHERE corresponding_question = $1 AND ($2 OR NOT hidden)
+                    AND ($3::timestamp IS NULL OR (created_on, id) > ($3, $4))
+                ORDER BY created_on, id
+                LIMIT $5",
+            ANSWER_COLUMNS
+        ))
+        .bind(question_id)
+        .bind(include_hidden)
+        .bind(after_created_on)
+        .bind(after_id)
+        .bind(page.limit + 1)
+        .map(answer_from_row)
+        .fetch_all(&self.connection)
+        .await?;
+
+        let total: i64 = sqlx::query(
+            "SELECT COUNT(*) AS total FROM answers
+                WHERE corresponding_question = $1 AND ($2 OR NOT hidden)",
+        )
+        .bind(question_id)
+        .bind(include_hidden)
+        .map(|row: PgRow| row.get("total"))
+        .fetch_one(&self.connection)
+        .await?;
+
+        Ok(Page::new(answers, page.limit, total, |answer| Cursor {
+            created_on: answer.created_on,
+            id: answer.id.0,
+            key: None,
+        }))
+    }
+
+    // Grabs the answers for a batch of questions in one query
+    // Answers are grouped by question and keep the listing order
+    pub async fn get_answers_for_questions(
+        &self,
+        question_ids: &[i32],
+        include_hidden: bool,
+    ) -> Result<HashMap<i32, Vec<Answer>>, MyError> {
+        let answers = sqlx::query(&format!(
+            "SELECT {} FROM answers WHERE corresponding_question = ANY($1) AND ($2 OR NOT hidden)
+                ORDER BY {}",
+            ANSWER_COLUMNS, ANSWER_ORDER
+        ))
+        .bind(question_ids)
+        .bind(include_hidden)
+        .map(answer_from_row)
+        .fetch_all(&self.connection)
+        .await?;
+
+        let mut grouped: HashMap<i32, Vec<Answer>> = HashMap::new();
+        for answer in answers {
+            grouped
+                .entry(answer.question_id.0)
+                .or_default()
+                .push(answer);
+        }
+
+        Ok(grouped)
+    }
+
+    // Full text search over question titles, question content and answer content
+    // Returns one page of hits, best match first, and the total number of matches
+    pub async fn search_questions(
+        &self,
+        query: &str,
+        limit: Option<i32>,
+        offset: i32,
+    ) -> Result<(Vec<SearchHit>, i64), MyError> {
+        let rows = sqlx::query(
+            "WITH search AS (SELECT websearch_to_tsquery('english', $1) AS query),
+            matches AS (
+                SELECT questions.id,
+                    ts_rank(questions.search, search.query)
+                        + COALESCE((SELECT MAX(ts_rank(answers.search, search.query)) FROM answers
+                            WHERE answers.corresponding_question = questions.id
+                            AND NOT answers.hidden
+                            AND answers.search @@ search.query), 0) AS rank
+                FROM questions, search
+                WHERE NOT questions.hidden AND questions.deleted_at IS NULL
+                    AND (questions.search @@ search.query
+                        OR EXISTS (SELECT 1 FROM answers
+                            WHERE answers.corresponding_question = questions.id
+                            AND NOT answers.hidden
+                            AND answers.search @@ search.query))
+            )
+            SELECT questions.id, questions.title, questions.content, questions.tags,
+                questions.created_on, questions.version, matches.rank,
+                ts_headline('english', translate(questions.title, $5, ''), search.query, $4)
+                    AS title_snippet,
+                ts_headline('english', translate(questions.content, $5, ''), search.query, $4)
+                    AS content_snippet,
+                (SELECT ts_headline('english', translate(answers.content, $5, ''),
+                        search.query, $4) FROM answers
+                    WHERE answers.corresponding_question = questions.id
+                    AND NOT answers.hidden
+                    AND answers.search @@ search.query
+                    ORDER BY ts_rank(answers.search, search.query) DESC, answers.id
+                    LIMIT 1) AS answer_snippet,
+                COUNT(*) OVER () AS total
+            FROM matches
+            JOIN questions ON questions.id = matches.id
+            CROSS JOIN search
+            ORDER BY matches.rank DESC, questions.id
+            LIMIT $2 OFFSET $3",
+        )
+        .bind(query)
+        .bind(limit)
+        .bind(offset)
+        .bind(HEADLINE_OPTIONS)
+        .bind(String::from_iter([MATCH_START, MATCH_END]))
+        .fetch_all(&self.connection)
+        .await?;
+
+        let total = match rows.first() {
+            Some(row) => row.get("total"),
+            // Past the last page there are no rows to read the total from
+            None if offset > 0 => self.count_search_matches(query).await?,
+            None => 0,
+        };
+
+        let hits = rows
+            .into_iter()
+            .map(|row| SearchHit {
+                question: question_from_row(&row),
+                rank: row.get("rank"),
+                title_snippet: highlight(row.get("title_snippet")),
+                content_snippet: highlight(row.get("content_snippet")),
+                answer_snippet: row.get::<Option<&str>, _>("answer_snippet").map(highlight),
+            })
+            .collect();
+
+        Ok((hits, total))
+    }
+
+    async fn count_search_matches(&self, query: &str) -> Result<i64, MyError> {
+        Ok(sqlx::query(
+            "WITH search AS (SELECT websearch_to_tsquery('english', $1) AS query)
+            SELECT COUNT(*) AS total FROM questions, search
+            WHERE NOT questions.hidden AND questions.deleted_at IS NULL
+                AND (questions.search @@ search.query
+                    OR EXISTS (SELECT 1 FROM answers
+                        WHERE answers.corresponding_question = questions.id
+                        AND NOT answers.hidden
+                        AND answers.search @@ search.query))",
+        )
+        .bind(query)
+        .map(|row: PgRow| row.get("total"))
+        .fetch_one(&self.connection)
+        .await?)
+    }
+
+    // Adds a new question to the database, owned by the given account
+    pub async fn add_question(
+        &self,
+        new_question: NewQuestion,
+        account_id: AccountId,
+        flagged: bool,
+    ) -> Result<Question, sqlx::Error> {
         match sqlx::query(
-            "INSERT INTO questions (title, content, tags) VALUES ($1, $2, $3)
-                RETURNING id, title, content, tags",
+            "INSERT INTO questions (title, content, tags, account_id, flagged)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id, title, content, tags, created_on, version",
         )
         .bind(new_question.title)
         .bind(new_question.content)
-        .bind(new_question.tags)
-        .map(|row: PgRow| Question {
-            id: QuestionId(row.get("id")),
-            title: row.get("title"),
-            content: row.get("content"),
-            tags: row.get("tags"),
-        })
+        .bind(normalize_tags(new_question.tags))
+        .bind(account_id.0)
+        .bind(flagged)
+        .map(|row: PgRow| question_from_row(&row))
         .fetch_one(&self.connection)
         .await
         {
@@ -221,79 +759,1232 @@ impl Store {
         }
     }
 
-    // Updates a question in the data base
+    // Updates a question in the data base and saves the new text as a revision
+    // Fails with PreconditionFailed unless the question is still at the expected version
+    // A flag set earlier stays until the question has been reviewed
     pub async fn update_question(
         &self,
         question: Question,
         question_id: i32,
-    ) -> Result<Question, sqlx::Error> {
-        match sqlx::query(
+        flagged: bool,
+        editor: &AccountId,
+        expected: &IfMatch,
+    ) -> Result<Question, MyError> {
+        let text = NewQuestion {
+            title: question.title,
+            content: question.content,
+            tags: normalize_tags(question.tags),
+        };
+        self.revise_question(question_id, text, flagged, editor, expected)
+            .await
+    }
+
+    // Saves the text as the next revision of the question, tags have to be normalized already
+    // Rollbacks use it directly with the text of the earlier revision
+    pub async fn revise_question(
+        &self,
+        question_id: i32,
+        text: NewQuestion,
+        flagged: bool,
+        editor: &AccountId,
+        expected: &IfMatch,
+    ) -> Result<Question, MyError> {
+        let mut tx = self.connection.begin().await?;
+
+        // Locks the question so concurrent edits get consecutive revision numbers
+        // and the version cannot change between the check and the update
+        let version: i32 = sqlx::query(
+            "SELECT version FROM questions WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        )
+        .bind(question_id)
+        .map(|row: PgRow| row.get("version"))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(MyError::QuestionNotFound)?;
+        if !expected.matches(version) {
+            return Err(MyError::PreconditionFailed);
+        }
+
+        keep_first_question_revision(&mut tx, question_id).await?;
+
+        let question = sqlx::query(
             "UPDATE questions
-                SET title = $1, content = $2, tags = $3
+                SET title = $1, content = $2, tags = $3, flagged = flagged OR $5,
+                    version = version + 1
                 WHERE id = $4
-                RETURNING id, title, content, tags",
+                RETURNING id, title, content, tags, created_on, version",
+        )
+        .bind(&text.title)
+        .bind(&text.content)
+        .bind(&text.tags)
+        .bind(question_id)
+        .bind(flagged)
+        .map(|row: PgRow| question_from_row(&row))
+        .fetch_one(&mut *tx)
+        .await?;
+
+        add_question_revision(&mut tx, question_id, &text, editor).await?;
+
+        tx.commit().await?;
+        Ok(question)
+    }
+
+    // Every saved version of a question, oldest first
+    // Questions that were never edited have none
+    pub async fn get_question_revisions(
+        &self,
+        question_id: i32,
+    ) -> Result<Vec<QuestionRevision>, MyError> {
+        Ok(sqlx::query(
+            "SELECT revision, title, content, tags, account_id, created_on FROM question_revisions
+                WHERE question_id = $1
+                ORDER BY revision",
+        )
+        .bind(question_id)
+        .map(question_revision_from_row)
+        .fetch_all(&self.connection)
+        .await?)
+    }
+
+    pub async fn get_question_revision(
+        &self,
+        question_id: i32,
+        revision: i32,
+    ) -> Result<QuestionRevision, MyError> {
+        sqlx::query(
+            "SELECT revision, title, content, tags, account_id, created_on FROM question_revisions
+                WHERE question_id = $1 AND revision = $2",
         )
-        .bind(question.title)
-        .bind(question.content)
-        .bind(question.tags)
         .bind(question_id)
-        .map(|row: PgRow| Question {
-            id: QuestionId(row.get("id")),
+        .bind(revision)
+        .map(question_revision_from_row)
+        .fetch_optional(&self.connection)
+        .await?
+        .ok_or(MyError::RevisionNotFound)
+    }
+
+    // Deletes a question for good, its answers, votes and reports go with it in the same statement
+    // Soft deleted questions can be removed this way as well
+    pub async fn delete_question(
+        &self,
+        question_id: i32,
+        expected: &IfMatch,
+    ) -> Result<(), MyError> {
+        let result = sqlx::query(
+            "DELETE FROM questions WHERE id = $1 AND ($2::integer[] IS NULL OR version = ANY($2))",
+        )
+        .bind(question_id)
+        .bind(expected.versions())
+        .execute(&self.connection)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(self.missing_or_changed(question_id, false).await);
+        }
+        Ok(())
+    }
+
+    // Marks a question as deleted, it keeps its answers and votes until it is restored
+    // The version stays, restoring expects the ETag the question had when it was deleted
+    pub async fn soft_delete_question(
+        &self,
+        question_id: i32,
+        expected: &IfMatch,
+    ) -> Result<(), MyError> {
+        let result = sqlx::query(
+            "UPDATE questions SET deleted_at = NOW()
+                WHERE id = $1 AND deleted_at IS NULL
+                    AND ($2::integer[] IS NULL OR version = ANY($2))",
+        )
+        .bind(question_id)
+        .bind(expected.versions())
+        .execute(&self.connection)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(self.missing_or_changed(question_id, false).await);
+        }
+        Ok(())
+    }
+
+    // Why a conditional write to a question changed nothing, `deleted` picks soft deleted ones
+    async fn missing_or_changed(&self, question_id: i32, deleted: bool) -> MyError {
+        match sqlx::query("SELECT 1 FROM questions WHERE id = $1 AND (deleted_at IS NOT NULL) = $2")
+            .bind(question_id)
+            .bind(deleted)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(Some(_)) => MyError::PreconditionFailed,
+            Ok(None) => MyError::QuestionNotFound,
+            Err(e) => MyError::DatabaseQueryError(e),
+        }
+    }
+
+    // Brings back a soft deleted question that is still at the expected version
+    pub async fn restore_question(
+        &self,
+        question_id: i32,
+        expected: &IfMatch,
+    ) -> Result<Question, MyError> {
+        match sqlx::query(
+            "UPDATE questions SET deleted_at = NULL, version = version + 1
+                WHERE id = $1 AND deleted_at IS NOT NULL
+                    AND ($2::integer[] IS NULL OR version = ANY($2))
+                RETURNING id, title, content, tags, created_on, version",
+        )
+        .bind(question_id)
+        .bind(expected.versions())
+        .map(|row: PgRow| question_from_row(&row))
+        .fetch_optional(&self.connection)
+        .await?
+        {
+            Some(question) => Ok(question),
+            None => Err(self.missing_or_changed(question_id, true).await),
+        }
+    }
+
+    // Reads questions with an id above `after_id` together with their answers, in id order
+    // Used to export the whole corpus one batch at a time
+    pub async fn export_questions(
+        &self,
+        after_id: i32,
+        limit: i64,
+    ) -> Result<Vec<QuestionRecord>, MyError> {
+        let mut records = sqlx::query(
+            "SELECT questions.id, questions.title, questions.content, questions.tags,
+                    accounts.email AS account_email, questions.created_on,
+                    questions.hidden, questions.flagged
+                FROM questions
+                LEFT JOIN accounts ON accounts.id = questions.account_id
+                WHERE questions.id > $1 AND questions.deleted_at IS NULL
+                ORDER BY questions.id
+                LIMIT $2",
+        )
+        .bind(after_id)
+        .bind(limit)
+        .map(|row: PgRow| QuestionRecord {
+            id: Some(row.get("id")),
             title: row.get("title"),
             content: row.get("content"),
             tags: row.get("tags"),
+            account_email: row.get("account_email"),
+            account_id: None,
+            created_on: Some(row.get("created_on")),
+            hidden: Some(row.get("hidden")),
+            flagged: Some(row.get("flagged")),
+            answers: Vec::new(),
         })
-        .fetch_one(&self.connection)
-        .await
-        {
-            Ok(question) => Ok(question),
-            Err(e) => Err(e),
+        .fetch_all(&self.connection)
+        .await?;
+
+        let ids: Vec<i32> = records.iter().filter_map(|record| record.id).collect();
+        let answers = sqlx::query(
+            "SELECT answers.id, answers.content, answers.corresponding_question,
+                    answers.created_on, answers.accepted, accounts.email AS account_email,
+                    answers.hidden, answers.flagged
+                FROM answers
+                LEFT JOIN accounts ON accounts.id = answers.account_id
+                WHERE answers.corresponding_question = ANY($1)
+                ORDER BY answers.id",
+        )
+        .bind(&ids)
+        .map(|row: PgRow| {
+            (
+                row.get::<i32, _>("corresponding_question"),
+                AnswerRecord {
+                    id: Some(row.get("id")),
+                    content: row.get("content"),
+                    created_on: Some(row.get("created_on")),
+                    accepted: row.get("accepted"),
+                    account_email: row.get("account_email"),
+                    account_id: None,
+                    hidden: Some(row.get("hidden")),
+                    flagged: Some(row.get("flagged")),
+                },
+            )
+        })
+        .fetch_all(&self.connection)
+        .await?;
+
+        let mut grouped: HashMap<i32, Vec<AnswerRecord>> = HashMap::new();
+        for (question_id, answer) in answers {
+            grouped.entry(question_id).or_default().push(answer);
         }
+        for record in &mut records {
+            if let Some(answers) = record.id.and_then(|id| grouped.remove(&id)) {
+                record.answers = answers;
+            }
+        }
+
+        Ok(records)
     }
 
-    // Deletes question from database
-    pub async fn delete_question(&self, question_id: i32) -> Result<bool, sqlx::Error> {
-        match sqlx::query("DELETE FROM questions WHERE id = $1")
-            .bind(question_id)
-            .execute(&self.connection)
-            .await
-        {
-            Ok(_) => Ok(true),
-            Err(e) => Err(e),
+    // The question each of the given answer ids belongs to, ids that are not taken are left out
+    pub async fn answer_questions(&self, answer_ids: &[i32]) -> Result<HashMap<i32, i32>, MyError> {
+        let rows = sqlx::query("SELECT id, corresponding_question FROM answers WHERE id = ANY($1)")
+            .bind(answer_ids)
+            .map(|row: PgRow| (row.get("id"), row.get("corresponding_question")))
+            .fetch_all(&self.connection)
+            .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    // Ids of the accounts registered with the given emails, unknown emails are left out
+    pub async fn account_ids_by_email(
+        &self,
+        emails: &[String],
+    ) -> Result<HashMap<String, i32>, MyError> {
+        let rows = sqlx::query("SELECT id, email FROM accounts WHERE email = ANY($1)")
+            .bind(emails)
+            .map(|row: PgRow| (row.get("email"), row.get("id")))
+            .fetch_all(&self.connection)
+            .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    // Inserts or updates every record in one transaction, nothing is written if one fails
+    // Owners have to be resolved to local accounts by the caller, account_id is written as given
+    // Overwrites keep the stored owner without an account_email and the stored time without created_on
+    // Overwritten questions and answers get a revision by the importing account
+    // Returns the id of each question and whether it was newly created
+    pub async fn import_questions(
+        &self,
+        records: &[QuestionRecord],
+        editor: &AccountId,
+    ) -> Result<Vec<(i32, bool)>, MyError> {
+        let mut tx = self.connection.begin().await?;
+
+        // Generated ids must not run into ids carried by the imported records
+        let max_question_id = records.iter().filter_map(|r| r.id).max().unwrap_or(0);
+        let max_answer_id = records
+            .iter()
+            .flat_map(|r| r.answers.iter().filter_map(|a| a.id))
+            .max()
+            .unwrap_or(0);
+        for (table, max_id) in [("questions", max_question_id), ("answers", max_answer_id)] {
+            sqlx::query(&format!(
+                "SELECT setval(pg_get_serial_sequence('{table}', 'id'),
+                    GREATEST(COALESCE((SELECT MAX(id) FROM {table}), 0), $1) + 1, false)"
+            ))
+            .bind(max_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let mut outcomes = Vec::with_capacity(records.len());
+        for record in records {
+            let text = NewQuestion {
+                title: record.title.clone(),
+                content: record.content.clone(),
+                tags: normalize_tags(record.tags.clone()),
+            };
+            if let Some(id) = record.id {
+                lock_question(&mut tx, id).await?;
+                keep_first_question_revision(&mut tx, id).await?;
+            }
+
+            let (question_id, created): (i32, bool) = sqlx::query(
+                "INSERT INTO questions (id, title, content, tags, account_id, created_on, hidden, flagged)
+                    VALUES (COALESCE($1, nextval(pg_get_serial_sequence('questions', 'id'))),
+                        $2, $3, $4, $5, COALESCE($6, NOW()), COALESCE($7, FALSE), COALESCE($8, FALSE))
+                    ON CONFLICT (id) DO UPDATE
+                    SET title = EXCLUDED.title, content = EXCLUDED.content,
+                        tags = EXCLUDED.tags,
+                        account_id = CASE WHEN $9 THEN EXCLUDED.account_id ELSE questions.account_id END,
+                        created_on = COALESCE($6, questions.created_on),
+                        version = questions.version + 1,
+                        hidden = COALESCE($7, questions.hidden),
+                        flagged = COALESCE($8, questions.flagged)
+                    RETURNING id, (xmax = 0) AS created",
+            )
+            .bind(record.id)
+            .bind(&text.title)
+            .bind(&text.content)
+            .bind(&text.tags)
+            .bind(record.account_id)
+            .bind(record.created_on)
+            .bind(record.hidden)
+            .bind(record.flagged)
+            .bind(record.account_email.is_some())
+            .map(|row: PgRow| (row.get("id"), row.get("created")))
+            .fetch_one(&mut *tx)
+            .await?;
+            if !created {
+                add_question_revision(&mut tx, question_id, &text, editor).await?;
+            }
+
+            // Only one answer per question may be accepted
+            if record.answers.iter().any(|answer| answer.accepted) {
+                sqlx::query(
+                    "UPDATE answers SET accepted = FALSE WHERE corresponding_question = $1",
+                )
+                .bind(question_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            for answer in &record.answers {
+                if let Some(id) = answer.id {
+                    lock_answer(&mut tx, id).await?;
+                    keep_first_answer_revision(&mut tx, id).await?;
+                }
+
+                let (answer_id, created): (i32, bool) = sqlx::query(
+                    "INSERT INTO answers
+                            (id, content, corresponding_question, created_on, accepted, account_id,
+                                hidden, flagged)
+                        VALUES (COALESCE($1, nextval(pg_get_serial_sequence('answers', 'id'))),
+                            $2, $3, COALESCE($4, NOW()), $5, $6,
+                            COALESCE($7, FALSE), COALESCE($8, FALSE))
+                        ON CONFLICT (id) DO UPDATE
+                        SET content = EXCLUDED.content,
+                            created_on = COALESCE($4, answers.created_on),
+                            account_id = CASE WHEN $9 THEN EXCLUDED.account_id ELSE answers.account_id END,
+                            hidden = COALESCE($7, answers.hidden),
+                            flagged = COALESCE($8, answers.flagged),
+                            accepted = EXCLUDED.accepted AND NOT COALESCE($7, answers.hidden)
+                        RETURNING id, (xmax = 0) AS created",
+                )
+                .bind(answer.id)
+                .bind(&answer.content)
+                .bind(question_id)
+                .bind(answer.created_on)
+                .bind(answer.accepted)
+                .bind(answer.account_id)
+                .bind(answer.hidden)
+                .bind(answer.flagged)
+                .bind(answer.account_email.is_some())
+                .map(|row: PgRow| (row.get("id"), row.get("created")))
+                .fetch_one(&mut *tx)
+                .await?;
+                if !created {
+                    add_answer_revision(&mut tx, answer_id, &answer.content, editor).await?;
+                }
+            }
+
+            outcomes.push((question_id, created));
         }
+
+        crate::seed::fix_sequences(&mut tx).await?;
+        tx.commit().await?;
+        Ok(outcomes)
     }
 
     // Adds answer to the data base by matching the answer id to the question id
-    pub async fn add_answer(&self, new_answer: NewAnswer) -> Result<Answer, MyError> {
-        
-
-        match sqlx::query("INSERT INTO answers (content, corresponding_question) VALUES ($1, $2) RETURNING id, content, corresponding_question")
+    pub async fn add_answer(
+        &self,
+        new_answer: NewAnswer,
+        account_id: AccountId,
+        flagged: bool,
+    ) -> Result<Answer, MyError> {
+        match sqlx::query(&format!(
+            "INSERT INTO answers (content, corresponding_question, account_id, flagged) VALUES ($1, $2, $3, $4) RETURNING {}",
+            ANSWER_COLUMNS
+        ))
             .bind(new_answer.content)
             .bind(new_answer.question_id.0)
-            .map(|row: PgRow| Answer {
-                id: AnswerId(row.get("id")),
-                content: row.get("content"),
-                question_id: QuestionId(row.get("corresponding_question")),
-            })
+            .bind(account_id.0)
+            .bind(flagged)
+            .map(answer_from_row)
             .fetch_one(&self.connection)
             .await
         {
             Ok(answer) => Ok(answer),
-            Err(_e) => {
-                Err(MyError::DatabaseQueryError)
+            // The question was deleted after the caller looked it up
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(MyError::QuestionNotFound)
             }
+            Err(e) => Err(MyError::DatabaseQueryError(e)),
+        }
+    }
+
+    // Replaces the content of an answer and saves it as a revision
+    // A flag set earlier stays until the answer has been reviewed
+    pub async fn update_answer(
+        &self,
+        answer_id: i32,
+        content: String,
+        flagged: bool,
+        editor: &AccountId,
+    ) -> Result<Answer, MyError> {
+        let mut tx = self.connection.begin().await?;
+
+        // Locks the answer so concurrent edits get consecutive revision numbers
+        // Answers to a soft deleted question count as missing
+        sqlx::query(
+            "SELECT answers.id FROM answers
+                JOIN questions ON questions.id = answers.corresponding_question
+                WHERE answers.id = $1 AND questions.deleted_at IS NULL
+                FOR UPDATE OF answers",
+        )
+        .bind(answer_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(MyError::AnswerNotFound)?;
+
+        keep_first_answer_revision(&mut tx, answer_id).await?;
+
+        let answer = sqlx::query(&format!(
+            "UPDATE answers SET content = $1, flagged = flagged OR $3 WHERE id = $2 RETURNING {}",
+            ANSWER_COLUMNS
+        ))
+        .bind(&content)
+        .bind(answer_id)
+        .bind(flagged)
+        .map(answer_from_row)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        add_answer_revision(&mut tx, answer_id, &content, editor).await?;
+
+        tx.commit().await?;
+        Ok(answer)
+    }
+
+    // Every saved version of an answer, oldest first
+    pub async fn get_answer_revisions(
+        &self,
+        answer_id: i32,
+    ) -> Result<Vec<AnswerRevision>, MyError> {
+        Ok(sqlx::query(
+            "SELECT revision, content, account_id, created_on FROM answer_revisions
+                WHERE answer_id = $1
+                ORDER BY revision",
+        )
+        .bind(answer_id)
+        .map(answer_revision_from_row)
+        .fetch_all(&self.connection)
+        .await?)
+    }
+
+    pub async fn get_answer_revision(
+        &self,
+        answer_id: i32,
+        revision: i32,
+    ) -> Result<AnswerRevision, MyError> {
+        sqlx::query(
+            "SELECT revision, content, account_id, created_on FROM answer_revisions
+                WHERE answer_id = $1 AND revision = $2",
+        )
+        .bind(answer_id)
+        .bind(revision)
+        .map(answer_revision_from_row)
+        .fetch_optional(&self.connection)
+        .await?
+        .ok_or(MyError::RevisionNotFound)
+    }
+
+    // Deletes a single answer, its votes and reports go with it
+    pub async fn delete_answer(&self, answer_id: i32) -> Result<(), MyError> {
+        let result = sqlx::query("DELETE FROM answers WHERE id = $1")
+            .bind(answer_id)
+            .execute(&self.connection)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(MyError::AnswerNotFound);
+        }
+        Ok(())
+    }
+
+    // Checks whether the account created the question, a soft deleted one counts as missing
+    pub async fn is_question_owner(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, MyError> {
+        self.question_owner(question_id, account_id, false).await
+    }
+
+    // Checks whether the account created the soft deleted question
+    pub async fn is_deleted_question_owner(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, MyError> {
+        self.question_owner(question_id, account_id, true).await
+    }
+
+    async fn question_owner(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+        deleted: bool,
+    ) -> Result<bool, MyError> {
+        match sqlx::query(
+            "SELECT account_id FROM questions WHERE id = $1 AND (deleted_at IS NOT NULL) = $2",
+        )
+        .bind(question_id)
+        .bind(deleted)
+        .map(|row: PgRow| row.get::<Option<i32>, _>("account_id"))
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(owner)) => Ok(owner == Some(account_id.0)),
+            Ok(None) => Err(MyError::QuestionNotFound),
+            Err(e) => Err(MyError::DatabaseQueryError(e)),
         }
     }
 
-    // Deletes answer from database
-    pub async fn delete_answer(&self, question_id: i32) -> Result<bool, sqlx::Error> {
-        match sqlx::query("DELETE FROM answers WHERE corresponding_question = $1")
+    // Checks whether the account wrote the answer, one to a soft deleted question counts as missing
+    pub async fn is_answer_owner(
+        &self,
+        answer_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, MyError> {
+        match sqlx::query(
+            "SELECT account_id FROM answers WHERE id = $1
+                AND corresponding_question IN (SELECT id FROM questions WHERE deleted_at IS NULL)",
+        )
+        .bind(answer_id)
+        .map(|row: PgRow| row.get::<Option<i32>, _>("account_id"))
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(owner)) => Ok(owner == Some(account_id.0)),
+            Ok(None) => Err(MyError::AnswerNotFound),
+            Err(e) => Err(MyError::DatabaseQueryError(e)),
+        }
+    }
+
+    // Returns a single answer by id, a hidden one counts as missing unless asked for
+    // Answers to a soft deleted question are always missing
+    pub async fn get_answer(
+        &self,
+        answer_id: i32,
+        include_hidden: bool,
+    ) -> Result<Answer, MyError> {
+        match sqlx::query(&format!(
+            "SELECT {} FROM answers WHERE id = $1 AND ($2 OR NOT hidden)
+                AND corresponding_question IN (SELECT id FROM questions WHERE deleted_at IS NULL)",
+            ANSWER_COLUMNS
+        ))
+        .bind(answer_id)
+        .bind(include_hidden)
+        .map(answer_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(answer) => Ok(answer),
+            Err(sqlx::Error::RowNotFound) => Err(MyError::AnswerNotFound),
+            Err(e) => Err(MyError::DatabaseQueryError(e)),
+        }
+    }
+
+    // Sums the votes of a batch of questions, questions without votes are left out
+    pub async fn get_question_scores(
+        &self,
+        question_ids: &[i32],
+    ) -> Result<HashMap<i32, i64>, MyError> {
+        let scores = sqlx::query(
+            "SELECT question_id, SUM(value) AS score FROM question_votes
+                WHERE question_id = ANY($1)
+                GROUP BY question_id",
+        )
+        .bind(question_ids)
+        .map(|row: PgRow| (row.get("question_id"), row.get("score")))
+        .fetch_all(&self.connection)
+        .await?;
+
+        Ok(scores.into_iter().collect())
+    }
+
+    // Records the account's vote on a question, voting again replaces the old vote
+    pub async fn vote_question(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+        value: i16,
+    ) -> Result<i64, MyError> {
+        sqlx::query(
+            "INSERT INTO question_votes (question_id, account_id, value) VALUES ($1, $2, $3)
+                ON CONFLICT (question_id, account_id)
+                DO UPDATE SET value = EXCLUDED.value, created_on = NOW()",
+        )
+        .bind(question_id)
+        .bind(account_id.0)
+        .bind(value)
+        .execute(&self.connection)
+        .await
+        .map_err(|e| missing_parent(e, MyError::QuestionNotFound))?;
+
+        self.question_score(question_id).await
+    }
+
+    // Removes the account's vote on a question
+    pub async fn remove_question_vote(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<i64, MyError> {
+        sqlx::query("DELETE FROM question_votes WHERE question_id = $1 AND account_id = $2")
             .bind(question_id)
+            .bind(account_id.0)
             .execute(&self.connection)
-            .await
+            .await?;
+
+        self.question_score(question_id).await
+    }
+
+    pub async fn question_score(&self, question_id: i32) -> Result<i64, MyError> {
+        Ok(sqlx::query(
+            "SELECT COALESCE(SUM(value), 0) AS score FROM question_votes WHERE question_id = $1",
+        )
+        .bind(question_id)
+        .map(|row: PgRow| row.get("score"))
+        .fetch_one(&self.connection)
+        .await?)
+    }
+
+    // Records the account's vote on an answer, voting again replaces the old vote
+    pub async fn vote_answer(
+        &self,
+        answer_id: i32,
+        account_id: &AccountId,
+        value: i16,
+    ) -> Result<i64, MyError> {
+        sqlx::query(
+            "INSERT INTO answer_votes (answer_id, account_id, value) VALUES ($1, $2, $3)
+                ON CONFLICT (answer_id, account_id)
+                DO UPDATE SET value = EXCLUDED.value, created_on = NOW()",
+        )
+        .bind(answer_id)
+        .bind(account_id.0)
+        .bind(value)
+        .execute(&self.connection)
+        .await
+        .map_err(|e| missing_parent(e, MyError::AnswerNotFound))?;
+
+        self.answer_score(answer_id).await
+    }
+
+    // Removes the account's vote on an answer
+    pub async fn remove_answer_vote(
+        &self,
+        answer_id: i32,
+        account_id: &AccountId,
+    ) -> Result<i64, MyError> {
+        sqlx::query("DELETE FROM answer_votes WHERE answer_id = $1 AND account_id = $2")
+            .bind(answer_id)
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await?;
+
+        self.answer_score(answer_id).await
+    }
+
+    pub async fn answer_score(&self, answer_id: i32) -> Result<i64, MyError> {
+        Ok(sqlx::query(
+            "SELECT COALESCE(SUM(value), 0) AS score FROM answer_votes WHERE answer_id = $1",
+        )
+        .bind(answer_id)
+        .map(|row: PgRow| row.get("score"))
+        .fetch_one(&self.connection)
+        .await?)
+    }
+
+    // Marks one answer as accepted and clears any answer accepted before it
+    pub async fn accept_answer(&self, answer_id: i32, question_id: i32) -> Result<Answer, MyError> {
+        let mut tx = self.connection.begin().await?;
+
+        sqlx::query(
+            "UPDATE answers SET accepted = FALSE WHERE corresponding_question = $1 AND accepted",
+        )
+        .bind(question_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let answer = sqlx::query(&format!(
+            "UPDATE answers SET accepted = TRUE WHERE id = $1 RETURNING {}",
+            ANSWER_COLUMNS
+        ))
+        .bind(answer_id)
+        .map(answer_from_row)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(answer)
+    }
+
+    // Clears the accepted mark from an answer
+    pub async fn unaccept_answer(&self, answer_id: i32) -> Result<Answer, MyError> {
+        match sqlx::query(&format!(
+            "UPDATE answers SET accepted = FALSE WHERE id = $1 RETURNING {}",
+            ANSWER_COLUMNS
+        ))
+        .bind(answer_id)
+        .map(answer_from_row)
+        .fetch_one(&self.connection)
+        .await
         {
-            Ok(_) => Ok(true),
-            Err(e) => Err(e),
+            Ok(answer) => Ok(answer),
+            Err(sqlx::Error::RowNotFound) => Err(MyError::AnswerNotFound),
+            Err(e) => Err(MyError::DatabaseQueryError(e)),
         }
     }
+    // Files a report against a visible question
+    pub async fn report_question(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+        reason: String,
+    ) -> Result<Report, MyError> {
+        sqlx::query(
+            "INSERT INTO reports (question_id, account_id, reason)
+                SELECT id, $2, $3 FROM questions WHERE id = $1 AND NOT hidden AND deleted_at IS NULL
+                RETURNING id, account_id, reason, created_on",
+        )
+        .bind(question_id)
+        .bind(account_id.0)
+        .bind(reason)
+        .map(report_from_row)
+        .fetch_optional(&self.connection)
+        .await?
+        .ok_or(MyError::QuestionNotFound)
+    }
+
+    // Files a report against a visible answer, one to a soft deleted question counts as missing
+    pub async fn report_answer(
+        &self,
+        answer_id: i32,
+        account_id: &AccountId,
+        reason: String,
+    ) -> Result<Report, MyError> {
+        sqlx::query(
+            "INSERT INTO reports (answer_id, account_id, reason)
+                SELECT id, $2, $3 FROM answers WHERE id = $1 AND NOT hidden
+                    AND corresponding_question IN (SELECT id FROM questions WHERE deleted_at IS NULL)
+                RETURNING id, account_id, reason, created_on",
+        )
+        .bind(answer_id)
+        .bind(account_id.0)
+        .bind(reason)
+        .map(report_from_row)
+        .fetch_optional(&self.connection)
+        .await?
+        .ok_or(MyError::AnswerNotFound)
+    }
+
+    // Posts flagged by the moderator or carrying open reports
+    pub async fn get_review_queue(&self) -> Result<Vec<ReviewItem>, MyError> {
+        self.review_items(
+            "flagged OR EXISTS (SELECT 1 FROM reports
+                WHERE reports.{kind}_id = {table}.id AND resolved_on IS NULL)",
+        )
+        .await
+    }
+
+    // Every hidden post, so moderators can still find them
+    pub async fn get_hidden_posts(&self) -> Result<Vec<ReviewItem>, MyError> {
+        self.review_items("hidden").await
+    }
+
+    // Loads the questions and answers matching the condition with their open reports
+    // {table} and {kind} in the condition are filled in for questions and answers
+    async fn review_items(&self, condition: &str) -> Result<Vec<ReviewItem>, MyError> {
+        let questions_condition = condition
+            .replace("{table}", "questions")
+            .replace("{kind}", "question");
+        let answers_condition = condition
+            .replace("{table}", "answers")
+            .replace("{kind}", "answer");
+
+        let mut items = sqlx::query(&format!(
+            "SELECT id, title, content, flagged, hidden FROM questions
+                WHERE {} AND deleted_at IS NULL ORDER BY id",
+            questions_condition
+        ))
+        .map(|row: PgRow| ReviewItem {
+            kind: PostKind::Question,
+            id: row.get("id"),
+            question_id: row.get("id"),
+            title: Some(row.get("title")),
+            content: row.get("content"),
+            flagged: row.get("flagged"),
+            hidden: row.get("hidden"),
+            reports: Vec::new(),
+        })
+        .fetch_all(&self.connection)
+        .await?;
+
+        items.extend(
+            sqlx::query(&format!(
+                "SELECT id, corresponding_question, content, flagged, hidden FROM answers
+                    WHERE {} AND corresponding_question IN
+                        (SELECT id FROM questions WHERE deleted_at IS NULL)
+                    ORDER BY id",
+                answers_condition
+            ))
+            .map(|row: PgRow| ReviewItem {
+                kind: PostKind::Answer,
+                id: row.get("id"),
+                question_id: row.get("corresponding_question"),
+                title: None,
+                content: row.get("content"),
+                flagged: row.get("flagged"),
+                hidden: row.get("hidden"),
+                reports: Vec::new(),
+            })
+            .fetch_all(&self.connection)
+            .await?,
+        );
+
+        let question_ids: Vec<i32> = items
+            .iter()
+            .filter(|item| item.kind == PostKind::Question)
+            .map(|item| item.id)
+            .collect();
+        let answer_ids: Vec<i32> = items
+            .iter()
+            .filter(|item| item.kind == PostKind::Answer)
+            .map(|item| item.id)
+            .collect();
+
+        let reports = sqlx::query(
+            "SELECT id, question_id, answer_id, account_id, reason, created_on FROM reports
+                WHERE resolved_on IS NULL AND (question_id = ANY($1) OR answer_id = ANY($2))
+                ORDER BY created_on, id",
+        )
+        .bind(&question_ids)
+        .bind(&answer_ids)
+        .fetch_all(&self.connection)
+        .await?;
+
+        let mut grouped: HashMap<(PostKind, i32), Vec<Report>> = HashMap::new();
+        for row in reports {
+            let key = match row.get::<Option<i32>, _>("question_id") {
+                Some(id) => (PostKind::Question, id),
+                None => (PostKind::Answer, row.get("answer_id")),
+            };
+            grouped.entry(key).or_default().push(report_from_row(row));
+        }
+        for item in &mut items {
+            item.reports = grouped.remove(&(item.kind, item.id)).unwrap_or_default();
+        }
+
+        Ok(items)
+    }
+
+    // Applies a moderator decision to a question and closes its open reports
+    // Deleting also removes the answers, votes and reports that belong to it
+    pub async fn resolve_question(
+        &self,
+        question_id: i32,
+        resolution: Resolution,
+        moderator: &AccountId,
+    ) -> Result<Resolved, MyError> {
+        let mut tx = self.connection.begin().await?;
+
+        let reports = if resolution == Resolution::Deleted {
+            let deleted = sqlx::query("DELETE FROM questions WHERE id = $1")
+                .bind(question_id)
+                .execute(&mut *tx)
+                .await?;
+            if deleted.rows_affected() == 0 {
+                return Err(MyError::QuestionNotFound);
+            }
+            0
+        } else {
+            let updated = sqlx::query(
+                "UPDATE questions SET flagged = FALSE, hidden = $2
+                        WHERE id = $1 AND deleted_at IS NULL",
+            )
+            .bind(question_id)
+            .bind(resolution == Resolution::Hidden)
+            .execute(&mut *tx)
+            .await?;
+            if updated.rows_affected() == 0 {
+                return Err(MyError::QuestionNotFound);
+            }
+            sqlx::query(
+                "UPDATE reports SET resolved_on = NOW(), resolved_by = $2, resolution = $3
+                    WHERE question_id = $1 AND resolved_on IS NULL",
+            )
+            .bind(question_id)
+            .bind(moderator.0)
+            .bind(resolution.as_str())
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+        };
+
+        tx.commit().await?;
+        Ok(Resolved {
+            kind: PostKind::Question,
+            id: question_id,
+            resolution,
+            reports,
+        })
+    }
+
+    // Applies a moderator decision to an answer and closes its open reports
+    // Answers to a soft deleted question count as missing
+    pub async fn resolve_answer(
+        &self,
+        answer_id: i32,
+        resolution: Resolution,
+        moderator: &AccountId,
+    ) -> Result<Resolved, MyError> {
+        let mut tx = self.connection.begin().await?;
+
+        let reports = if resolution == Resolution::Deleted {
+            let deleted = sqlx::query(
+                "DELETE FROM answers WHERE id = $1
+                    AND corresponding_question IN (SELECT id FROM questions WHERE deleted_at IS NULL)",
+            )
+            .bind(answer_id)
+            .execute(&mut *tx)
+            .await?;
+            if deleted.rows_affected() == 0 {
+                return Err(MyError::AnswerNotFound);
+            }
+            0
+        } else {
+            // A hidden answer cannot stay the accepted one
+            let updated = sqlx::query(
+                "UPDATE answers SET flagged = FALSE, hidden = $2, accepted = accepted AND NOT $2
+                    WHERE id = $1
+                    AND corresponding_question IN (SELECT id FROM questions WHERE deleted_at IS NULL)",
+            )
+            .bind(answer_id)
+            .bind(resolution == Resolution::Hidden)
+            .execute(&mut *tx)
+            .await?;
+            if updated.rows_affected() == 0 {
+                return Err(MyError::AnswerNotFound);
+            }
+            sqlx::query(
+                "UPDATE reports SET resolved_on = NOW(), resolved_by = $2, resolution = $3
+                    WHERE answer_id = $1 AND resolved_on IS NULL",
+            )
+            .bind(answer_id)
+            .bind(moderator.0)
+            .bind(resolution.as_str())
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+        };
+
+        tx.commit().await?;
+        Ok(Resolved {
+            kind: PostKind::Answer,
+            id: answer_id,
+            resolution,
+            reports,
+        })
+    }
+}
+
+// Filtered questions with the values they can be sorted by, ready for ORDER BY
+fn question_listing() -> String {
+    format!(
+        "SELECT * FROM (
+            SELECT questions.*,
+                (SELECT COUNT(*) FROM answers
+                    WHERE answers.corresponding_question = questions.id
+                    AND ($3 OR NOT answers.hidden)) AS answer_count,
+                (SELECT COALESCE(SUM(value), 0) FROM question_votes
+                    WHERE question_votes.question_id = questions.id) AS score
+            FROM questions
+            WHERE {}
+        ) AS listing WHERE TRUE",
+        QUESTION_FILTER
+    )
+}
+
+fn bind_question_filter<'q>(
+    query: sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>,
+    filter: &'q QuestionFilter,
+    include_hidden: bool,
+) -> sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments> {
+    query
+        .bind(&filter.tags.tags)
+        .bind(filter.tags.mode == TagMatch::All)
+        .bind(include_hidden)
+        .bind(filter.unanswered)
+        .bind(filter.author.as_ref().map(|author| author.0))
+        .bind(filter.created_after)
+        .bind(filter.created_before)
+}
+
+// Columns the listing is ordered by, created_on and id always break ties
+fn sort_order(sort: QuestionSort) -> &'static str {
+    match sort {
+        QuestionSort::Oldest => "created_on, id",
+        QuestionSort::Newest => "created_on DESC, id DESC",
+        QuestionSort::MostAnswers => "answer_count DESC, created_on DESC, id DESC",
+        QuestionSort::Score => "score DESC, created_on DESC, id DESC",
+    }
+}
+
+// Rows after the cursor in the listing order, $8 created_on, $9 id, $10 count or score
+fn sort_after(sort: QuestionSort) -> &'static str {
+    match sort {
+        QuestionSort::Oldest => "(created_on, id) > ($8, $9)",
+        QuestionSort::Newest => "(created_on, id) < ($8, $9)",
+        QuestionSort::MostAnswers => "(answer_count, created_on, id) < ($10, $8, $9)",
+        QuestionSort::Score => "(score, created_on, id) < ($10, $8, $9)",
+    }
+}
+
+// Keyset bounds for a page, both NULL on the first page
+fn cursor_bounds(page: &CursorPage) -> (Option<NaiveDateTime>, Option<i32>) {
+    match page.after {
+        Some(cursor) => (Some(cursor.created_on), Some(cursor.id)),
+        None => (None, None),
+    }
+}
+
+fn question_from_row(row: &PgRow) -> Question {
+    Question {
+        id: QuestionId(row.get("id")),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        created_on: row.get("created_on"),
+        version: row.get("version"),
+    }
+}
+
+// Keeps an imported overwrite from racing an edit for the same revision number
+async fn lock_question(connection: &mut PgConnection, question_id: i32) -> Result<(), MyError> {
+    sqlx::query("SELECT id FROM questions WHERE id = $1 FOR UPDATE")
+        .bind(question_id)
+        .execute(connection)
+        .await?;
+    Ok(())
+}
+
+async fn lock_answer(connection: &mut PgConnection, answer_id: i32) -> Result<(), MyError> {
+    sqlx::query("SELECT id FROM answers WHERE id = $1 FOR UPDATE")
+        .bind(answer_id)
+        .execute(connection)
+        .await?;
+    Ok(())
+}
+
+// The text as first posted becomes revision 1 on the first edit
+// The question has to be locked by the caller
+async fn keep_first_question_revision(
+    connection: &mut PgConnection,
+    question_id: i32,
+) -> Result<(), MyError> {
+    sqlx::query(
+        "INSERT INTO question_revisions
+            (question_id, revision, title, content, tags, account_id, created_on)
+            SELECT id, 1, title, content, tags, account_id, created_on FROM questions
+            WHERE id = $1
+                AND NOT EXISTS (SELECT 1 FROM question_revisions WHERE question_id = $1)",
+    )
+    .bind(question_id)
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
+async fn add_question_revision(
+    connection: &mut PgConnection,
+    question_id: i32,
+    text: &NewQuestion,
+    editor: &AccountId,
+) -> Result<(), MyError> {
+    sqlx::query(
+        "INSERT INTO question_revisions (question_id, revision, title, content, tags, account_id)
+            SELECT $1, COALESCE(MAX(revision), 0) + 1, $2, $3, $4, $5
+            FROM question_revisions WHERE question_id = $1",
+    )
+    .bind(question_id)
+    .bind(&text.title)
+    .bind(&text.content)
+    .bind(&text.tags)
+    .bind(editor.0)
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
+// The text as first posted becomes revision 1 on the first edit
+// The answer has to be locked by the caller
+async fn keep_first_answer_revision(
+    connection: &mut PgConnection,
+    answer_id: i32,
+) -> Result<(), MyError> {
+    sqlx::query(
+        "INSERT INTO answer_revisions (answer_id, revision, content, account_id, created_on)
+            SELECT id, 1, content, account_id, created_on FROM answers
+            WHERE id = $1
+                AND NOT EXISTS (SELECT 1 FROM answer_revisions WHERE answer_id = $1)",
+    )
+    .bind(answer_id)
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
+async fn add_answer_revision(
+    connection: &mut PgConnection,
+    answer_id: i32,
+    content: &str,
+    editor: &AccountId,
+) -> Result<(), MyError> {
+    sqlx::query(
+        "INSERT INTO answer_revisions (answer_id, revision, content, account_id)
+            SELECT $1, COALESCE(MAX(revision), 0) + 1, $2, $3
+            FROM answer_revisions WHERE answer_id = $1",
+    )
+    .bind(answer_id)
+    .bind(content)
+    .bind(editor.0)
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
+fn question_revision_from_row(row: PgRow) -> QuestionRevision {
+    QuestionRevision {
+        revision: row.get("revision"),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        editor: row.get::<Option<i32>, _>("account_id").map(AccountId),
+        created_on: row.get("created_on"),
+    }
+}
+
+fn answer_revision_from_row(row: PgRow) -> AnswerRevision {
+    AnswerRevision {
+        revision: row.get("revision"),
+        content: row.get("content"),
+        editor: row.get::<Option<i32>, _>("account_id").map(AccountId),
+        created_on: row.get("created_on"),
+    }
+}
+
+fn answer_from_row(row: PgRow) -> Answer {
+    Answer {
+        id: AnswerId(row.get("id")),
+        content: row.get("content"),
+        question_id: QuestionId(row.get("corresponding_question")),
+        created_on: row.get("created_on"),
+        accepted: row.get("accepted"),
+        score: row.get("score"),
+    }
+}
+
+fn report_from_row(row: PgRow) -> Report {
+    Report {
+        id: row.get("id"),
+        account_id: AccountId(row.get("account_id")),
+        reason: row.get("reason"),
+        created_on: row.get("created_on"),
+    }
+}
+
+// A foreign key violation means the parent row does not exist
+fn missing_parent(error: sqlx::Error, not_found: MyError) -> MyError {
+    match &error {
+        sqlx::Error::Database(db_error) if db_error.is_foreign_key_violation() => not_found,
+        _ => MyError::DatabaseQueryError(error),
+    }
 }
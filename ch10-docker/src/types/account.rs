@@ -1,11 +1,36 @@
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::types::error::MyError;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Session {
     pub exp: DateTime<Utc>,
     pub account_id: AccountId,
     pub nbf: DateTime<Utc>,
+    // Tokens issued before roles existed carry none, they belong to plain users
+    #[serde(default)]
+    pub role: Role,
+    // Login the token belongs to, revoking it invalidates the token
+    pub family: i32,
+}
+
+impl Session {
+    // True when the account has at least the given role
+    pub fn has_role(&self, role: Role) -> bool {
+        self.role >= role
+    }
+
+    // Guard for handlers limited to some roles, fails with 403 otherwise
+    pub fn require(&self, role: Role) -> Result<(), MyError> {
+        if self.has_role(role) {
+            Ok(())
+        } else {
+            Err(MyError::Forbidden)
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -13,12 +38,214 @@ pub struct Account {
     pub id: Option<AccountId>,
     pub email: String,
     pub password: String,
+    #[serde(default)]
+    pub role: Role,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AccountId(pub i32);
+// Body of /registration and /login, anything else (like an id) is refused
 #[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct NewAccount {
     pub email: String,
     pub password: String,
 }
+
+// Body of a password change
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordChange {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+// Body of an account deletion, the password is asked for again
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AccountDeletion {
+    pub password: String,
+}
+
+// Body of /password/forgot
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordForgot {
+    pub email: String,
+}
+
+// Body of /password/reset, the token comes from the reset mail
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordReset {
+    pub token: String,
+    pub new_password: String,
+}
+
+// Longest email that fits accounts.email, also the limit from RFC 5321
+const EMAIL_MAX_LENGTH: usize = 254;
+
+// Emails are the account key, so they are trimmed and lower cased before every lookup
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+// Checks the rough shape of an email, local@domain.tld
+// Whether it really exists is only known once mail is sent to it
+pub fn validate_email(email: &str) -> Result<(), MyError> {
+    let invalid = || MyError::ValidationError(format!("{:?} is not a valid email address", email));
+
+    if email.len() > EMAIL_MAX_LENGTH || email.chars().any(|c| c.is_whitespace() || c.is_control())
+    {
+        return Err(invalid());
+    }
+    let (local, domain) = email.split_once('@').ok_or_else(invalid)?;
+    if local.is_empty() || local.len() > 64 || domain.contains('@') {
+        return Err(invalid());
+    }
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return Err(invalid());
+    }
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2 {
+        return Err(invalid());
+    }
+    for label in &labels {
+        if label.is_empty()
+            || label.len() > 63
+            || label.starts_with('-')
+            || label.ends_with('-')
+            || !label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        {
+            return Err(invalid());
+        }
+    }
+    Ok(())
+}
+
+// What an account may do, every role includes the ones before it
+#[derive(
+    Deserialize,
+    Serialize,
+    clap::ValueEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Default,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    // Works the review queue and may delete anyone's posts
+    Moderator,
+    // Also seeds, exports, imports and hands out roles
+    Admin,
+}
+
+impl Role {
+    // Value stored in accounts.role
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = MyError;
+
+    fn from_str(role: &str) -> Result<Role, MyError> {
+        match role {
+            "user" => Ok(Role::User),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(MyError::ValidationError(format!(
+                "role must be user, moderator or admin, got {:?}",
+                role
+            ))),
+        }
+    }
+}
+
+// Body of a role change
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RoleChange {
+    pub role: Role,
+}
+
+// An account without its password, returned by /account and after a role change
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AccountProfile {
+    pub id: AccountId,
+    pub email: String,
+    pub role: Role,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_email_trims_and_lower_cases() {
+        assert_eq!(
+            normalize_email("  Jane.Doe@Example.COM \n"),
+            "jane.doe@example.com"
+        );
+    }
+
+    #[test]
+    fn validate_email_accepts_usual_addresses() {
+        for email in [
+            "jane@example.com",
+            "jane.doe+tag@mail.example.co.uk",
+            "j_d-1@sub-domain.example.org",
+        ] {
+            assert!(validate_email(email).is_ok(), "{} should be valid", email);
+        }
+    }
+
+    #[test]
+    fn validate_email_rejects_malformed_addresses() {
+        let long_local = format!("{}@example.com", "a".repeat(65));
+        let long_email = format!("a@{}.com", "b".repeat(250));
+        for email in [
+            "",
+            "jane",
+            "@example.com",
+            "jane@",
+            "jane@example",
+            "jane@@example.com",
+            "ja ne@example.com",
+            ".jane@example.com",
+            "jane.@example.com",
+            "ja..ne@example.com",
+            "jane@-example.com",
+            "jane@example-.com",
+            "jane@exa_mple.com",
+            "jane@example..com",
+            long_local.as_str(),
+            long_email.as_str(),
+        ] {
+            assert!(
+                matches!(validate_email(email), Err(MyError::ValidationError(_))),
+                "{:?} should be invalid",
+                email
+            );
+        }
+    }
+}
@@ -1,12 +1,17 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-use crate::types::questions::QuestionId;
+use crate::types::error::MyError;
+use crate::types::questions::{check_content, QuestionId};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Answer {
     pub id: AnswerId,
     pub content: String,
     pub question_id: QuestionId,
+    pub created_on: NaiveDateTime,
+    pub accepted: bool,
+    pub score: i64,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
@@ -17,3 +22,24 @@ pub struct NewAnswer {
     pub content: String,
     pub question_id: QuestionId,
 }
+
+impl NewAnswer {
+    pub fn validated(self) -> Result<NewAnswer, MyError> {
+        check_content("content", &self.content).map_err(MyError::ValidationError)?;
+        Ok(self)
+    }
+}
+
+// Body of PUT /answers/:id, only the content can change
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateAnswer {
+    pub content: String,
+}
+
+impl UpdateAnswer {
+    pub fn validated(self) -> Result<UpdateAnswer, MyError> {
+        check_content("content", &self.content).map_err(MyError::ValidationError)?;
+        Ok(self)
+    }
+}
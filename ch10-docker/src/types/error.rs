@@ -0,0 +1,238 @@
+use argon2::Error as ArgonError;
+use axum::{
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::time::Duration;
+
+// Postgres error codes we translate into client errors
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+const CHECK_VIOLATION: &str = "23514";
+// Value too long for its column, e.g. a title over VARCHAR(255)
+const STRING_TOO_LONG: &str = "22001";
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum MyError {
+    ParseError(std::num::ParseIntError),
+    MissingParameters,
+    QuestionNotFound,
+    AnswerNotFound,
+    AccountNotFound,
+    RevisionNotFound,
+    DatabaseQueryError(sqlx::Error),
+    WrongPassword,
+    SerializationError,
+    ArgonLibraryError(ArgonError),
+    Unauthorized,
+    Forbidden,
+    ValidationError(String),
+    ContentRejected(Vec<String>),
+    ExternalAPIError(reqwest::Error),
+    MailError(String),
+    // If-Match did not match the current version
+    PreconditionFailed,
+    // A write that needs If-Match came without it
+    PreconditionRequired,
+    // Carries how long the client has to wait
+    TooManyRequests(Duration),
+}
+
+// Body returned for every error, clients can branch on `code`
+#[derive(Serialize, Debug)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl MyError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            MyError::ParseError(_) | MyError::MissingParameters => StatusCode::BAD_REQUEST,
+            MyError::QuestionNotFound
+            | MyError::AnswerNotFound
+            | MyError::AccountNotFound
+            | MyError::RevisionNotFound => StatusCode::NOT_FOUND,
+            MyError::WrongPassword | MyError::Unauthorized => StatusCode::UNAUTHORIZED,
+            MyError::Forbidden => StatusCode::FORBIDDEN,
+            MyError::ValidationError(_) | MyError::ContentRejected(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            MyError::ExternalAPIError(_) | MyError::MailError(_) => StatusCode::BAD_GATEWAY,
+            MyError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            MyError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            MyError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            MyError::DatabaseQueryError(e) => match DatabaseFailure::of(e) {
+                DatabaseFailure::NotFound => StatusCode::NOT_FOUND,
+                DatabaseFailure::Duplicate => StatusCode::CONFLICT,
+                DatabaseFailure::MissingReference | DatabaseFailure::Rejected => {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                }
+                DatabaseFailure::Other => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            MyError::SerializationError | MyError::ArgonLibraryError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    // Stable identifier for the error, never change an existing one
+    pub fn code(&self) -> &'static str {
+        match self {
+            MyError::ParseError(_) => "invalid_parameter",
+            MyError::MissingParameters => "missing_parameters",
+            MyError::QuestionNotFound => "question_not_found",
+            MyError::AnswerNotFound => "answer_not_found",
+            MyError::AccountNotFound => "account_not_found",
+            MyError::RevisionNotFound => "revision_not_found",
+            MyError::WrongPassword => "wrong_credentials",
+            MyError::Unauthorized => "unauthorized",
+            MyError::Forbidden => "forbidden",
+            MyError::ValidationError(_) => "validation_failed",
+            MyError::ContentRejected(_) => "content_rejected",
+            MyError::ExternalAPIError(_) => "external_api_error",
+            MyError::MailError(_) => "mail_error",
+            MyError::TooManyRequests(_) => "too_many_requests",
+            MyError::PreconditionFailed => "precondition_failed",
+            MyError::PreconditionRequired => "precondition_required",
+            MyError::DatabaseQueryError(e) => match DatabaseFailure::of(e) {
+                DatabaseFailure::NotFound => "not_found",
+                DatabaseFailure::Duplicate => "already_exists",
+                DatabaseFailure::MissingReference => "missing_reference",
+                DatabaseFailure::Rejected => "validation_failed",
+                DatabaseFailure::Other => "database_error",
+            },
+            MyError::SerializationError => "serialization_error",
+            MyError::ArgonLibraryError(_) => "internal_error",
+        }
+    }
+
+    // Human readable message, internal details are only logged
+    pub fn message(&self) -> String {
+        match self {
+            MyError::ParseError(e) => format!("Failed to parse integer: {}", e),
+            MyError::MissingParameters => "Missing parameters".to_string(),
+            MyError::QuestionNotFound => "Question Not Found".to_string(),
+            MyError::AnswerNotFound => "Answer Not Found".to_string(),
+            MyError::AccountNotFound => "Account Not Found".to_string(),
+            MyError::RevisionNotFound => "Revision Not Found".to_string(),
+            MyError::WrongPassword => "Wrong email or password".to_string(),
+            MyError::Unauthorized => "Missing or invalid token".to_string(),
+            MyError::Forbidden => "Not allowed to access this resource".to_string(),
+            MyError::ValidationError(reason) => reason.clone(),
+            MyError::ContentRejected(words) => {
+                format!(
+                    "Content contains words that are not allowed: {}",
+                    words.join(", ")
+                )
+            }
+            MyError::ExternalAPIError(_) => "External service unavailable".to_string(),
+            MyError::MailError(_) => "Could not send mail".to_string(),
+            MyError::TooManyRequests(_) => format!(
+                "Too many attempts, try again in {} seconds",
+                self.retry_after().unwrap_or(1)
+            ),
+            MyError::PreconditionFailed => {
+                "Changed in the meantime, fetch it again and retry".to_string()
+            }
+            MyError::PreconditionRequired => "If-Match header is required".to_string(),
+            MyError::DatabaseQueryError(e) => match DatabaseFailure::of(e) {
+                DatabaseFailure::NotFound => "Resource Not Found".to_string(),
+                DatabaseFailure::Duplicate => "Resource already exists".to_string(),
+                DatabaseFailure::MissingReference => {
+                    "Referenced resource does not exist".to_string()
+                }
+                DatabaseFailure::Rejected => "Value rejected by the database".to_string(),
+                DatabaseFailure::Other => "Database Query Error".to_string(),
+            },
+            MyError::SerializationError => "Serialization Error".to_string(),
+            MyError::ArgonLibraryError(_) => "Internal Server Error".to_string(),
+        }
+    }
+
+    // Whole seconds for the Retry-After header, rounded up so clients never retry too early
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            MyError::TooManyRequests(wait) => {
+                let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                Some(seconds.max(1))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Which kind of database failure a sqlx error represents
+enum DatabaseFailure {
+    NotFound,
+    Duplicate,
+    MissingReference,
+    Rejected,
+    Other,
+}
+
+impl DatabaseFailure {
+    fn of(error: &sqlx::Error) -> DatabaseFailure {
+        match error {
+            sqlx::Error::RowNotFound => DatabaseFailure::NotFound,
+            sqlx::Error::Database(db_error) => match db_error.code().as_deref() {
+                Some(UNIQUE_VIOLATION) => DatabaseFailure::Duplicate,
+                Some(FOREIGN_KEY_VIOLATION) => DatabaseFailure::MissingReference,
+                Some(CHECK_VIOLATION) | Some(STRING_TOO_LONG) => DatabaseFailure::Rejected,
+                _ => DatabaseFailure::Other,
+            },
+            _ => DatabaseFailure::Other,
+        }
+    }
+}
+
+// Custom error type implementation, converts to a JSON response
+impl IntoResponse for MyError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::event!(tracing::Level::ERROR, "{:?}", self);
+        } else {
+            tracing::event!(tracing::Level::DEBUG, "{:?}", self);
+        }
+
+        let body = ErrorBody {
+            code: self.code(),
+            message: self.message(),
+        };
+        let mut response = (status, Json(body)).into_response();
+        if let Some(seconds) = self.retry_after() {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
+    }
+}
+
+impl From<sqlx::Error> for MyError {
+    fn from(err: sqlx::Error) -> MyError {
+        MyError::DatabaseQueryError(err)
+    }
+}
+
+impl From<reqwest::Error> for MyError {
+    fn from(err: reqwest::Error) -> MyError {
+        MyError::ExternalAPIError(err)
+    }
+}
+
+impl From<ArgonError> for MyError {
+    fn from(err: ArgonError) -> MyError {
+        MyError::ArgonLibraryError(err)
+    }
+}
+
+impl From<std::num::ParseIntError> for MyError {
+    fn from(err: std::num::ParseIntError) -> MyError {
+        MyError::ParseError(err)
+    }
+}
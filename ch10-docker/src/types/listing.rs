@@ -0,0 +1,338 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use std::str::FromStr;
+
+use crate::types::account::AccountId;
+use crate::types::error::MyError;
+use crate::types::pagination::{paging, Cursor, Paging};
+use crate::types::tags::{normalize_tag, TagFilter, TagMatch};
+
+// Orders of the question listings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuestionSort {
+    // Oldest first, the order the listings always had
+    #[default]
+    Oldest,
+    Newest,
+    // Most answers first, newest first among equals
+    MostAnswers,
+    // Best voted first, newest first among equals
+    Score,
+}
+
+impl QuestionSort {
+    // Sorts by answer count or score carry that value in their cursors
+    pub fn is_keyed(&self) -> bool {
+        matches!(self, QuestionSort::MostAnswers | QuestionSort::Score)
+    }
+}
+
+impl FromStr for QuestionSort {
+    type Err = MyError;
+
+    fn from_str(sort: &str) -> Result<QuestionSort, MyError> {
+        match sort {
+            "oldest" => Ok(QuestionSort::Oldest),
+            "newest" => Ok(QuestionSort::Newest),
+            "most_answers" => Ok(QuestionSort::MostAnswers),
+            "score" => Ok(QuestionSort::Score),
+            _ => Err(MyError::ValidationError(format!(
+                "sort must be newest, oldest, most_answers or score, got {:?}",
+                sort
+            ))),
+        }
+    }
+}
+
+// Conditions a question listing is narrowed down by, the default lets everything through
+#[derive(Debug, Clone, Default)]
+pub struct QuestionFilter {
+    pub tags: TagFilter,
+    // Only questions nobody has answered yet
+    pub unanswered: bool,
+    pub author: Option<AccountId>,
+    // created_after is inclusive, created_before exclusive
+    pub created_after: Option<NaiveDateTime>,
+    pub created_before: Option<NaiveDateTime>,
+}
+
+// Query string of the question listings
+#[derive(Debug, Default)]
+pub struct QuestionQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub cursor: Option<Cursor>,
+    pub sort: QuestionSort,
+    pub filter: QuestionFilter,
+}
+
+impl QuestionQuery {
+    // Parses and checks the query string, unknown parameters are refused
+    // Tags may be repeated (?tag=rust&tag=axum) and match=all requires every tag
+    pub fn parse(params: Vec<(String, String)>) -> Result<QuestionQuery, MyError> {
+        let mut query = QuestionQuery::default();
+
+        for (key, value) in params {
+            match key.as_str() {
+                "limit" => query.limit = Some(value.trim().parse::<i64>()?),
+                "offset" => query.offset = Some(value.trim().parse::<i64>()?),
+                "cursor" => {
+                    if !value.trim().is_empty() {
+                        query.cursor = Some(Cursor::decode(&value)?);
+                    }
+                }
+                "sort" => query.sort = value.trim().parse()?,
+                "tag" => {
+                    let tag = normalize_tag(&value);
+                    if !tag.is_empty() && !query.filter.tags.tags.contains(&tag) {
+                        query.filter.tags.tags.push(tag);
+                    }
+                }
+                "match" => {
+                    query.filter.tags.mode = match value.as_str() {
+                        "any" => TagMatch::Any,
+                        "all" => TagMatch::All,
+                        _ => {
+                            return Err(MyError::ValidationError(
+                                "match must be either any or all".to_string(),
+                            ))
+                        }
+                    }
+                }
+                "unanswered" => query.filter.unanswered = parse_bool(&key, &value)?,
+                "author" => query.filter.author = Some(AccountId(value.trim().parse::<i32>()?)),
+                "created_after" => query.filter.created_after = Some(parse_time(&key, &value)?),
+                "created_before" => query.filter.created_before = Some(parse_time(&key, &value)?),
+                _ => {
+                    return Err(MyError::ValidationError(format!(
+                        "unknown query parameter {:?}",
+                        key
+                    )))
+                }
+            }
+        }
+
+        if let (Some(after), Some(before)) =
+            (query.filter.created_after, query.filter.created_before)
+        {
+            if after >= before {
+                return Err(MyError::ValidationError(
+                    "created_after must be before created_before".to_string(),
+                ));
+            }
+        }
+        // A cursor only makes sense for the order it was handed out with
+        if let Some(cursor) = &query.cursor {
+            if cursor.key.is_some() != query.sort.is_keyed() {
+                return Err(MyError::ValidationError(
+                    "cursor belongs to a different sort".to_string(),
+                ));
+            }
+        }
+
+        Ok(query)
+    }
+
+    pub fn paging(&self) -> Result<Paging, MyError> {
+        paging(self.limit, self.offset, self.cursor)
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, MyError> {
+    match value.trim() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(MyError::ValidationError(format!(
+            "{} must be true or false",
+            key
+        ))),
+    }
+}
+
+// Accepts 2024-06-01, 2024-06-01T12:00:00 or RFC 3339 with an offset, which is converted to UTC
+fn parse_time(key: &str, value: &str) -> Result<NaiveDateTime, MyError> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.naive_utc());
+    }
+    if let Ok(time) = NaiveDateTime::from_str(value) {
+        return Ok(time);
+    }
+    if let Ok(date) = NaiveDate::from_str(value) {
+        return Ok(date.and_time(Default::default()));
+    }
+    Err(MyError::ValidationError(format!(
+        "{} must be a date like 2024-06-01 or a time like 2024-06-01T12:00:00Z",
+        key
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(params: &[(&str, &str)]) -> Result<QuestionQuery, MyError> {
+        QuestionQuery::parse(
+            params
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    fn invalid(result: Result<QuestionQuery, MyError>) -> String {
+        match result {
+            Err(MyError::ValidationError(message)) => message,
+            other => panic!("expected ValidationError, got {:?}", other),
+        }
+    }
+
+    fn time(text: &str) -> NaiveDateTime {
+        NaiveDateTime::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn empty_query_is_the_default_listing() {
+        let query = parse(&[]).unwrap();
+        assert_eq!(query.sort, QuestionSort::Oldest);
+        assert!(query.cursor.is_none() && query.limit.is_none() && query.offset.is_none());
+        assert!(query.filter.tags.tags.is_empty());
+        assert!(!query.filter.unanswered);
+    }
+
+    #[test]
+    fn reads_every_parameter() {
+        let query = parse(&[
+            ("limit", "5"),
+            ("sort", "score"),
+            ("tag", " Rust "),
+            ("tag", "rust"),
+            ("tag", "axum"),
+            ("match", "all"),
+            ("unanswered", "1"),
+            ("author", "7"),
+            ("created_after", "2024-06-01"),
+            ("created_before", "2024-07-01T00:00:00Z"),
+        ])
+        .unwrap();
+        assert_eq!(query.limit, Some(5));
+        assert_eq!(query.sort, QuestionSort::Score);
+        assert_eq!(query.filter.tags.tags, ["rust", "axum"]);
+        assert_eq!(query.filter.tags.mode, TagMatch::All);
+        assert!(query.filter.unanswered);
+        assert_eq!(query.filter.author.map(|author| author.0), Some(7));
+        assert_eq!(
+            query.filter.created_after,
+            Some(time("2024-06-01T00:00:00"))
+        );
+        assert_eq!(
+            query.filter.created_before,
+            Some(time("2024-07-01T00:00:00"))
+        );
+    }
+
+    #[test]
+    fn unknown_parameters_are_refused() {
+        let message = invalid(parse(&[("limit", "5"), ("page", "2")]));
+        assert!(message.contains("page"), "{}", message);
+    }
+
+    #[test]
+    fn bad_values_are_refused() {
+        invalid(parse(&[("sort", "random")]));
+        invalid(parse(&[("match", "some")]));
+        invalid(parse(&[("unanswered", "yes")]));
+        invalid(parse(&[("cursor", "not a cursor")]));
+    }
+
+    #[test]
+    fn author_has_to_be_a_number() {
+        assert!(matches!(
+            parse(&[("author", "jane")]),
+            Err(MyError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn cursor_has_to_match_the_sort() {
+        let plain = Cursor {
+            created_on: time("2024-06-01T12:00:00"),
+            id: 3,
+            key: None,
+        }
+        .encode();
+        let keyed = Cursor {
+            key: Some(4),
+            ..Cursor::decode(&plain).unwrap()
+        }
+        .encode();
+
+        assert!(parse(&[("cursor", &plain)]).is_ok());
+        assert!(parse(&[("sort", "newest"), ("cursor", &plain)]).is_ok());
+        assert!(parse(&[("sort", "score"), ("cursor", &keyed)]).is_ok());
+        invalid(parse(&[("sort", "most_answers"), ("cursor", &plain)]));
+        invalid(parse(&[("sort", "oldest"), ("cursor", &keyed)]));
+        // The order of the parameters does not matter
+        invalid(parse(&[("cursor", &plain), ("sort", "score")]));
+    }
+
+    #[test]
+    fn created_after_has_to_be_before_created_before() {
+        invalid(parse(&[
+            ("created_after", "2024-06-01"),
+            ("created_before", "2024-06-01"),
+        ]));
+        invalid(parse(&[
+            ("created_after", "2024-06-02"),
+            ("created_before", "2024-06-01"),
+        ]));
+        assert!(parse(&[
+            ("created_after", "2024-06-01"),
+            ("created_before", "2024-06-01T00:00:01"),
+        ])
+        .is_ok());
+    }
+
+    #[test]
+    fn parse_time_accepts_dates_local_times_and_offsets() {
+        assert_eq!(
+            parse_time("t", "2024-06-01").unwrap(),
+            time("2024-06-01T00:00:00")
+        );
+        assert_eq!(
+            parse_time("t", " 2024-06-01T12:30:00 ").unwrap(),
+            time("2024-06-01T12:30:00")
+        );
+        assert_eq!(
+            parse_time("t", "2024-06-01T12:30:00.250").unwrap(),
+            time("2024-06-01T12:30:00.250")
+        );
+        assert_eq!(
+            parse_time("t", "2024-06-01T12:30:00Z").unwrap(),
+            time("2024-06-01T12:30:00")
+        );
+        assert_eq!(
+            parse_time("t", "2024-06-01T14:30:00+02:00").unwrap(),
+            time("2024-06-01T12:30:00")
+        );
+    }
+
+    #[test]
+    fn parse_time_rejects_other_formats() {
+        for bad in [
+            "",
+            "yesterday",
+            "01.06.2024",
+            "2024-13-01",
+            "2024-06-31",
+            "2024-06-01 12",
+            "1717243800",
+        ] {
+            match parse_time("created_after", bad) {
+                Err(MyError::ValidationError(message)) => {
+                    assert!(message.starts_with("created_after"), "{}", message)
+                }
+                other => panic!("{:?} should be rejected, got {:?}", bad, other),
+            }
+        }
+    }
+}
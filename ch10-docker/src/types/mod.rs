@@ -1,4 +1,13 @@
 pub mod account;
 pub mod answer;
+pub mod error;
+pub mod listing;
 pub mod pagination;
 pub mod questions;
+pub mod report;
+pub mod revision;
+pub mod search;
+pub mod tags;
+pub mod token;
+pub mod transfer;
+pub mod vote;
@@ -1,45 +1,12 @@
-use axum::body::Body;
-
-use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
-};
-
+use crate::types::error::MyError;
+use chrono::{DateTime, NaiveDateTime};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::result::Result::Ok;
 
-#[allow(dead_code)]
-#[derive(Debug)]
-pub enum MyError {
-    ParseError(std::num::ParseIntError),
-    MissingParameters,
-    QuestionNotFound,
-    DatabaseQueryError,
-}
-
-// Custom error type implementation, converts to response
-impl IntoResponse for MyError {
-    fn into_response(self) -> Response {
-        match self {
-            MyError::ParseError(_) => Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(Body::from("Failed to parse integer"))
-                .unwrap(),
-            MyError::MissingParameters => Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(Body::from("Missing parameters"))
-                .unwrap(),
-            MyError::QuestionNotFound => Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(Body::from("Question Not Found"))
-                .unwrap(),
-            MyError::DatabaseQueryError => Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(Body::from("Database Query Error"))
-                .unwrap(),
-        }
-    }
-}
+// Page size used when the client does not ask for one, and the largest it may ask for
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
 
 // Pagination struct
 #[derive(Debug, Default)]
@@ -48,25 +15,252 @@ pub struct Pagination {
     pub offset: i32,
 }
 
-#[allow(dead_code)]
 // Formats pagination
+// Either parameter may be left out, no limit means everything after the offset
 pub fn extract_pagination(params: HashMap<String, String>) -> Result<Pagination, MyError> {
-    if params.contains_key("limit") && params.contains_key("offset") {
-        return Ok(Pagination {
-            limit: Some(
-                params
-                    .get("limit")
-                    .unwrap()
-                    .parse::<i32>()
-                    .map_err(MyError::ParseError)?,
-            ),
-
-            offset: params
-                .get("offset")
+    if !params.contains_key("limit") && !params.contains_key("offset") {
+        return Err(MyError::MissingParameters);
+    }
+
+    let limit = match params.get("limit") {
+        Some(limit) => Some(limit.parse::<i32>().map_err(MyError::ParseError)?),
+        None => None,
+    };
+    let offset = match params.get("offset") {
+        Some(offset) => offset.parse::<i32>().map_err(MyError::ParseError)?,
+        None => 0,
+    };
+    if limit.is_some_and(|limit| limit < 0) || offset < 0 {
+        return Err(MyError::ValidationError(
+            "limit and offset must not be negative".to_string(),
+        ));
+    }
+
+    Ok(Pagination {
+        limit: limit.map(|limit| limit.min(MAX_PAGE_SIZE as i32)),
+        offset,
+    })
+}
+
+// Position of the last item on a page, the next page starts right after it
+// Clients only ever see it encoded, as an opaque string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_on: NaiveDateTime,
+    pub id: i32,
+    // Answer count or score when the listing is sorted by one of them
+    pub key: Option<i64>,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let mut cursor = format!(
+            "{}:{}",
+            self.created_on.and_utc().timestamp_micros(),
+            self.id
+        );
+        if let Some(key) = self.key {
+            cursor.push_str(&format!(":{}", key));
+        }
+        hex::encode(cursor)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Cursor, MyError> {
+        let invalid = || MyError::ValidationError("invalid cursor".to_string());
+
+        let bytes = hex::decode(cursor.trim()).map_err(|_| invalid())?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let mut parts = text.split(':');
+        let (micros, id) = match (parts.next(), parts.next()) {
+            (Some(micros), Some(id)) => (micros, id),
+            _ => return Err(invalid()),
+        };
+        let key = match parts.next() {
+            Some(key) => Some(key.parse::<i64>().map_err(|_| invalid())?),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let created_on = micros
+            .parse::<i64>()
+            .ok()
+            .and_then(DateTime::from_timestamp_micros)
+            .ok_or_else(invalid)?
+            .naive_utc();
+        let id = id.parse::<i32>().map_err(|_| invalid())?;
+
+        Ok(Cursor {
+            created_on,
+            id,
+            key,
+        })
+    }
+}
+
+// Keyset paging, the page holds up to `limit` items following `after`
+#[derive(Debug, Clone, Copy)]
+pub struct CursorPage {
+    pub after: Option<Cursor>,
+    pub limit: i64,
+}
+
+// How the client pages through a listing
+#[derive(Debug)]
+pub enum Paging {
+    Cursor(CursorPage),
+    // limit/offset as before cursors existed, kept for older clients
+    Offset(Pagination),
+}
+
+// Reads ?cursor=...&limit=..., both optional, the first page has no cursor
+pub fn extract_cursor_page(params: &HashMap<String, String>) -> Result<CursorPage, MyError> {
+    let limit = match params.get("limit") {
+        Some(limit) => Some(limit.parse::<i64>().map_err(MyError::ParseError)?),
+        None => None,
+    };
+    let after = match params.get("cursor").map(|cursor| cursor.trim()) {
+        Some(cursor) if !cursor.is_empty() => Some(Cursor::decode(cursor)?),
+        _ => None,
+    };
+
+    cursor_page(limit, after)
+}
+
+fn cursor_page(limit: Option<i64>, after: Option<Cursor>) -> Result<CursorPage, MyError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(MyError::ValidationError(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    Ok(CursorPage { after, limit })
+}
+
+// An offset means offset paging, otherwise the listing is paged with cursors
+pub fn paging(
+    limit: Option<i64>,
+    offset: Option<i64>,
+    cursor: Option<Cursor>,
+) -> Result<Paging, MyError> {
+    let offset = match offset {
+        Some(offset) => offset,
+        None => return Ok(Paging::Cursor(cursor_page(limit, cursor)?)),
+    };
+    if cursor.is_some() {
+        return Err(MyError::ValidationError(
+            "cursor and offset cannot be used together".to_string(),
+        ));
+    }
+    if limit.is_some_and(|limit| limit < 0) || offset < 0 {
+        return Err(MyError::ValidationError(
+            "limit and offset must not be negative".to_string(),
+        ));
+    }
+
+    Ok(Paging::Offset(Pagination {
+        limit: limit.map(|limit| limit.min(MAX_PAGE_SIZE) as i32),
+        offset: i32::try_from(offset).unwrap_or(i32::MAX),
+    }))
+}
+
+// One page of a listing
+// next_cursor is null on the last page, total counts every item of the listing
+#[derive(Debug, Serialize, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub total: i64,
+}
+
+impl<T> Page<T> {
+    // Builds a page from up to limit + 1 rows, the extra row only shows that another page follows
+    pub fn new(
+        mut items: Vec<T>,
+        limit: i64,
+        total: i64,
+        cursor_of: impl Fn(&T) -> Cursor,
+    ) -> Page<T> {
+        let more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+        let next_cursor = match items.last() {
+            Some(last) if more => Some(cursor_of(last).encode()),
+            _ => None,
+        };
+
+        Page {
+            items,
+            next_cursor,
+            total,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            total: self.total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(key: Option<i64>) -> Cursor {
+        Cursor {
+            created_on: DateTime::from_timestamp_micros(1_718_000_000_123_456)
                 .unwrap()
-                .parse::<i32>()
-                .map_err(MyError::ParseError)?,
-        });
+                .naive_utc(),
+            id: 42,
+            key,
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        for key in [None, Some(0), Some(7), Some(-3)] {
+            let cursor = cursor(key);
+            assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn cursor_keeps_microseconds() {
+        let decoded = Cursor::decode(&cursor(None).encode()).unwrap();
+        assert_eq!(
+            decoded.created_on.and_utc().timestamp_subsec_micros(),
+            123_456
+        );
+    }
+
+    #[test]
+    fn cursor_is_opaque() {
+        let encoded = cursor(Some(5)).encode();
+        assert!(encoded.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn decode_rejects_garbage() {
+        for bad in [
+            "",
+            "not hex",
+            "abc",
+            &hex::encode("1718000000123456"),
+            &hex::encode("1718000000123456:x"),
+            &hex::encode("1718000000123456:42:x"),
+            &hex::encode("1718000000123456:42:1:2"),
+            &hex::encode("soon:42"),
+            &hex::encode([0xff, 0xfe]),
+        ] {
+            assert!(
+                matches!(Cursor::decode(bad), Err(MyError::ValidationError(_))),
+                "{:?} should be rejected",
+                bad
+            );
+        }
     }
-    Err(MyError::MissingParameters)
 }
@@ -1,5 +1,11 @@
+use crate::types::answer::Answer;
+use crate::types::error::MyError;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+// Longest title the questions table accepts
+pub const MAX_TITLE_LENGTH: usize = 255;
+
 // Question struct
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
 pub struct Question {
@@ -7,6 +13,33 @@ pub struct Question {
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    // Set by the database, ignored when a question is sent in
+    #[serde(default)]
+    pub created_on: NaiveDateTime,
+    // Goes up with every edit, updates send it back in If-Match instead
+    #[serde(default)]
+    pub version: i32,
+}
+
+// A single question together with every answer given to it
+#[derive(Debug, Serialize, Clone)]
+pub struct QuestionWithAnswers {
+    #[serde(flatten)]
+    pub question: Question,
+    pub score: i64,
+    pub answer_count: usize,
+    pub answers: Vec<Answer>,
+}
+
+impl QuestionWithAnswers {
+    pub fn new(question: Question, score: i64, answers: Vec<Answer>) -> Self {
+        QuestionWithAnswers {
+            question,
+            score,
+            answer_count: answers.len(),
+            answers,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
@@ -19,7 +52,6 @@ pub struct QuestionResponse {
     pub source: String,
 }
 
-
 impl Serialize for QuestionId {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -38,3 +70,42 @@ pub struct NewQuestion {
     pub content: String,
     pub tags: Option<Vec<String>>,
 }
+
+impl NewQuestion {
+    pub fn validated(self) -> Result<NewQuestion, MyError> {
+        check_title(&self.title).map_err(MyError::ValidationError)?;
+        check_content("content", &self.content).map_err(MyError::ValidationError)?;
+        Ok(self)
+    }
+}
+
+impl Question {
+    // Same rules as a new question, the id and version come from the URL and If-Match
+    pub fn validated(self) -> Result<Question, MyError> {
+        check_title(&self.title).map_err(MyError::ValidationError)?;
+        check_content("content", &self.content).map_err(MyError::ValidationError)?;
+        Ok(self)
+    }
+}
+
+// Rules every question title follows, on the API and on import
+pub fn check_title(title: &str) -> Result<(), String> {
+    if title.trim().is_empty() {
+        return Err("title must not be empty".to_string());
+    }
+    if title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(format!(
+            "title must be at most {} characters",
+            MAX_TITLE_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+// Question and answer text must not be blank, `field` names it in the message
+pub fn check_content(field: &str, content: &str) -> Result<(), String> {
+    if content.trim().is_empty() {
+        return Err(format!("{} must not be empty", field));
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::types::account::AccountId;
+use crate::types::error::MyError;
+
+// Longest reason accepted with a report
+const MAX_REASON_LENGTH: usize = 500;
+
+// Body of a report request
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NewReport {
+    pub reason: String,
+}
+
+impl NewReport {
+    pub fn validated(self) -> Result<String, MyError> {
+        let reason = self.reason.trim().to_string();
+        if reason.is_empty() {
+            return Err(MyError::ValidationError(
+                "reason must not be empty".to_string(),
+            ));
+        }
+        if reason.chars().count() > MAX_REASON_LENGTH {
+            return Err(MyError::ValidationError(format!(
+                "reason must be at most {} characters",
+                MAX_REASON_LENGTH
+            )));
+        }
+        Ok(reason)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub id: i32,
+    pub account_id: AccountId,
+    pub reason: String,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum PostKind {
+    Question,
+    Answer,
+}
+
+// A question or answer waiting for a moderator, with its open reports
+#[derive(Serialize, Debug, Clone)]
+pub struct ReviewItem {
+    pub kind: PostKind,
+    pub id: i32,
+    pub question_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub content: String,
+    pub flagged: bool,
+    pub hidden: bool,
+    pub reports: Vec<Report>,
+}
+
+// What a moderator decided about a post
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    // Visible again, flag and reports cleared
+    Approved,
+    // Left out of every public listing
+    Hidden,
+    // Removed together with its reports
+    Deleted,
+}
+
+impl Resolution {
+    // Value stored in reports.resolution, deleted posts take their reports with them
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Approved => "approved",
+            Resolution::Hidden => "hidden",
+            Resolution::Deleted => "deleted",
+        }
+    }
+}
+
+// Returned after a moderator acted on a post
+#[derive(Serialize, Debug, Clone)]
+pub struct Resolved {
+    pub kind: PostKind,
+    pub id: i32,
+    pub resolution: Resolution,
+    // Number of open reports closed by the decision
+    pub reports: u64,
+}
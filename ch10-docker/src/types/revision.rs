@@ -0,0 +1,203 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+
+use crate::types::account::AccountId;
+
+// One saved version of a question, editor is the account that wrote it
+#[derive(Serialize, Debug, Clone)]
+pub struct QuestionRevision {
+    pub revision: i32,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    pub editor: Option<AccountId>,
+    pub created_on: NaiveDateTime,
+}
+
+// One saved version of an answer
+#[derive(Serialize, Debug, Clone)]
+pub struct AnswerRevision {
+    pub revision: i32,
+    pub content: String,
+    pub editor: Option<AccountId>,
+    pub created_on: NaiveDateTime,
+}
+
+// Query string of the diff endpoints, ?from=1&to=3
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct DiffQuery {
+    pub from: i32,
+    pub to: i32,
+}
+
+// What changed between two revisions, fields that stayed the same are left out
+#[derive(Serialize, Debug, Clone)]
+pub struct RevisionDiff {
+    pub from: i32,
+    pub to: i32,
+    pub changes: Vec<FieldChange>,
+}
+
+// A unified diff of one field, tags are compared one per line
+#[derive(Serialize, Debug, Clone)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub diff: String,
+}
+
+impl QuestionRevision {
+    pub fn diff(&self, to: &QuestionRevision) -> RevisionDiff {
+        let tags = |revision: &QuestionRevision| {
+            revision
+                .tags
+                .iter()
+                .flatten()
+                .map(|tag| format!("{}\n", tag))
+                .collect::<String>()
+        };
+        let changes = [
+            diff_field("title", &self.title, &to.title, self.revision, to.revision),
+            diff_field(
+                "content",
+                &self.content,
+                &to.content,
+                self.revision,
+                to.revision,
+            ),
+            diff_field("tags", &tags(self), &tags(to), self.revision, to.revision),
+        ];
+
+        RevisionDiff {
+            from: self.revision,
+            to: to.revision,
+            changes: changes.into_iter().flatten().collect(),
+        }
+    }
+}
+
+impl AnswerRevision {
+    pub fn diff(&self, to: &AnswerRevision) -> RevisionDiff {
+        RevisionDiff {
+            from: self.revision,
+            to: to.revision,
+            changes: diff_field(
+                "content",
+                &self.content,
+                &to.content,
+                self.revision,
+                to.revision,
+            )
+            .into_iter()
+            .collect(),
+        }
+    }
+}
+
+fn diff_field(
+    field: &'static str,
+    old: &str,
+    new: &str,
+    from: i32,
+    to: i32,
+) -> Option<FieldChange> {
+    if old == new {
+        return None;
+    }
+    let diff = TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&format!("revision {}", from), &format!("revision {}", to))
+        .to_string();
+    Some(FieldChange { field, diff })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(revision: i32, content: &str) -> AnswerRevision {
+        AnswerRevision {
+            revision,
+            content: content.to_string(),
+            editor: None,
+            created_on: NaiveDateTime::default(),
+        }
+    }
+
+    fn question(revision: i32, title: &str, content: &str, tags: &[&str]) -> QuestionRevision {
+        QuestionRevision {
+            revision,
+            title: title.to_string(),
+            content: content.to_string(),
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            editor: None,
+            created_on: NaiveDateTime::default(),
+        }
+    }
+
+    fn content_diff(old: &str, new: &str) -> String {
+        let diff = answer(1, old).diff(&answer(2, new));
+        assert_eq!((diff.from, diff.to), (1, 2));
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].field, "content");
+        diff.changes[0].diff.clone()
+    }
+
+    #[test]
+    fn identical_text_has_no_changes() {
+        assert!(answer(1, "same\ntext\n")
+            .diff(&answer(2, "same\ntext\n"))
+            .changes
+            .is_empty());
+        let question = question(1, "Title", "Text\n", &["rust"]);
+        assert!(question.diff(&question).changes.is_empty());
+    }
+
+    #[test]
+    fn insertions_are_marked_with_plus() {
+        assert_eq!(
+            content_diff("one\nthree\n", "one\ntwo\nthree\n"),
+            "--- revision 1\n+++ revision 2\n@@ -1,2 +1,3 @@\n one\n+two\n three\n"
+        );
+    }
+
+    #[test]
+    fn deletions_are_marked_with_minus() {
+        assert_eq!(
+            content_diff("one\ntwo\nthree\n", "one\nthree\n"),
+            "--- revision 1\n+++ revision 2\n@@ -1,3 +1,2 @@\n one\n-two\n three\n"
+        );
+    }
+
+    #[test]
+    fn changed_lines_are_removed_and_added() {
+        assert_eq!(
+            content_diff("one\ntwo\nthree\nfour\n", "zero\none\n2\nthree\n"),
+            "--- revision 1\n+++ revision 2\n@@ -1,4 +1,4 @@\n+zero\n one\n-two\n+2\n three\n-four\n"
+        );
+    }
+
+    #[test]
+    fn question_diff_lists_only_changed_fields() {
+        let from = question(2, "Title", "Text\n", &["rust", "web"]);
+        let to = question(5, "Title", "Text\n", &["rust", "axum"]);
+        let diff = from.diff(&to);
+        assert_eq!((diff.from, diff.to), (2, 5));
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].field, "tags");
+        assert_eq!(
+            diff.changes[0].diff,
+            "--- revision 2\n+++ revision 5\n@@ -1,2 +1,2 @@\n rust\n-web\n+axum\n"
+        );
+
+        let retitled = question(6, "New title", "Other text\n", &["rust", "axum"]);
+        let fields: Vec<&str> = to
+            .diff(&retitled)
+            .changes
+            .iter()
+            .map(|change| change.field)
+            .collect();
+        assert_eq!(fields, ["title", "content"]);
+    }
+}
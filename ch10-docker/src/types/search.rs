@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::html::push_escaped;
+use crate::types::questions::Question;
+
+// ts_headline wraps matched words in these, they are stripped from the text beforehand
+pub const MATCH_START: char = '\u{2}';
+pub const MATCH_END: char = '\u{3}';
+
+// Options for ts_headline, the markers are turned into <mark></mark> by `highlight`
+pub const HEADLINE_OPTIONS: &str = "StartSel=\u{2}, StopSel=\u{3}, MaxWords=35, MinWords=15";
+
+// One matching question, snippets wrap matched words in <mark></mark>
+// Everything else in a snippet is HTML escaped, so it can be rendered as HTML
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub question: Question,
+    pub rank: f32,
+    pub title_snippet: String,
+    pub content_snippet: String,
+    pub answer_snippet: Option<String>,
+}
+
+// A page of search results, total counts every match regardless of paging
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchResults {
+    pub query: String,
+    pub total: i64,
+    pub items: Vec<SearchHit>,
+}
+
+// Turns a ts_headline snippet into safe HTML, the text is escaped and only the markers
+// around matched words become <mark></mark>
+pub fn highlight(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len());
+    for c in snippet.chars() {
+        match c {
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_END => html.push_str("</mark>"),
+            c => push_escaped(&mut html, c),
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_escapes_everything_but_the_markers() {
+        assert_eq!(
+            highlight("<img src=x onerror=\"alert('x')\"> & \u{2}ocean\u{3}"),
+            "&lt;img src=x onerror=&quot;alert(&#39;x&#39;)&quot;&gt; &amp; <mark>ocean</mark>"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+// How a list of requested tags is matched against a question's tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagMatch {
+    // Question has at least one of the tags
+    #[default]
+    Any,
+    // Question has every one of the tags
+    All,
+}
+
+// Tags a question listing is filtered by, empty means no filtering
+#[derive(Debug, Clone, Default)]
+pub struct TagFilter {
+    pub tags: Vec<String>,
+    pub mode: TagMatch,
+}
+
+// One entry of the tag catalogue
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TagCount {
+    pub tag: String,
+    pub questions: i64,
+}
+
+// Lower cases a tag and collapses whitespace, "  Rust   Web " becomes "rust web"
+pub fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// Normalizes every tag, drops empty ones and duplicates while keeping the order
+pub fn normalize_tags(tags: Option<Vec<String>>) -> Option<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.unwrap_or_default() {
+        let tag = normalize_tag(&tag);
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    if normalized.is_empty() {
+        None
+    } else {
+        Some(normalized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Option<Vec<String>> {
+        Some(tags.iter().map(|tag| tag.to_string()).collect())
+    }
+
+    #[test]
+    fn normalize_tag_lower_cases_and_collapses_whitespace() {
+        assert_eq!(normalize_tag("  Rust   Web "), "rust web");
+        assert_eq!(normalize_tag("Async\t\nIO"), "async io");
+        assert_eq!(normalize_tag("ÄRGER"), "ärger");
+        assert_eq!(normalize_tag("c++"), "c++");
+        assert_eq!(normalize_tag(" \t "), "");
+    }
+
+    #[test]
+    fn normalize_tags_drops_duplicates_keeping_the_first() {
+        assert_eq!(
+            normalize_tags(tags(&["Axum", "rust", " axum ", "RUST", "web"])),
+            tags(&["axum", "rust", "web"])
+        );
+    }
+
+    #[test]
+    fn normalize_tags_drops_empty_tags() {
+        assert_eq!(normalize_tags(tags(&["", "  ", "rust"])), tags(&["rust"]));
+    }
+
+    #[test]
+    fn no_tags_left_is_none() {
+        assert_eq!(normalize_tags(None), None);
+        assert_eq!(normalize_tags(tags(&[])), None);
+        assert_eq!(normalize_tags(tags(&[" ", "\t"])), None);
+    }
+
+    #[test]
+    fn normalizing_twice_changes_nothing() {
+        let once = normalize_tags(tags(&[" Rust  Web", "rust web", "Tokio"]));
+        assert_eq!(normalize_tags(once.clone()), once);
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+// Returned by /login and /refresh
+// The access token goes in the Authorization header, the refresh token buys the next pair
+#[derive(Serialize, Debug, Clone)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: &'static str,
+    // Seconds until the access token expires
+    pub expires_in: i64,
+}
+
+// Body of a refresh request
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+// Returned by /logout-all
+#[derive(Serialize, Debug, Clone)]
+pub struct LogoutSummary {
+    // Number of logins that were still active
+    pub revoked: u64,
+}
@@ -0,0 +1,253 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::types::questions::{check_content, check_title};
+use crate::types::tags::normalize_tags;
+
+// One question with its answers, as written by /admin/export and read by /admin/import
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuestionRecord {
+    #[serde(default, deserialize_with = "lenient_id")]
+    pub id: Option<i32>,
+    pub title: String,
+    pub content: String,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    // Owners are matched to local accounts by email, ids differ between databases
+    #[serde(default)]
+    pub account_email: Option<String>,
+    // Only read from older exports to warn that it is ignored, the import sets it from the email
+    #[serde(default, skip_serializing)]
+    pub account_id: Option<i32>,
+    #[serde(default)]
+    pub created_on: Option<NaiveDateTime>,
+    // Moderation state, records without it keep the state the post already has
+    #[serde(default)]
+    pub hidden: Option<bool>,
+    #[serde(default)]
+    pub flagged: Option<bool>,
+    #[serde(default)]
+    pub answers: Vec<AnswerRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnswerRecord {
+    #[serde(default, deserialize_with = "lenient_id")]
+    pub id: Option<i32>,
+    pub content: String,
+    #[serde(default)]
+    pub created_on: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub accepted: bool,
+    #[serde(default)]
+    pub account_email: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub account_id: Option<i32>,
+    #[serde(default)]
+    pub hidden: Option<bool>,
+    #[serde(default)]
+    pub flagged: Option<bool>,
+}
+
+// Older files written by earlier chapters store ids as strings, accept both
+fn lenient_id<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(i32),
+        Text(String),
+    }
+
+    match Option::<Id>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Id::Number(id)) => Ok(Some(id)),
+        Some(Id::Text(text)) => text
+            .trim()
+            .parse::<i32>()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("invalid id {:?}", text))),
+    }
+}
+
+impl QuestionRecord {
+    // Checks the record against the same rules the API applies, and cleans up the tags
+    pub fn validated(mut self) -> Result<QuestionRecord, String> {
+        if self.id.is_some_and(|id| id <= 0) {
+            return Err("id must be positive".to_string());
+        }
+        check_title(&self.title)?;
+        check_content("content", &self.content)?;
+        for answer in &self.answers {
+            if answer.id.is_some_and(|id| id <= 0) {
+                return Err("answer id must be positive".to_string());
+            }
+            check_content("answer content", &answer.content)?;
+            if answer.accepted && answer.hidden == Some(true) {
+                return Err("a hidden answer cannot be accepted".to_string());
+            }
+        }
+        if self.answers.iter().filter(|answer| answer.accepted).count() > 1 {
+            return Err("at most one answer can be accepted".to_string());
+        }
+
+        self.tags = normalize_tags(self.tags);
+        Ok(self)
+    }
+}
+
+// Outcome of one imported record
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    // Passed validation but was not written because another record failed
+    Valid,
+    Created,
+    Updated,
+    Invalid,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportResult {
+    // Position of the record in the uploaded file, starting at 0
+    pub index: usize,
+    pub id: Option<i32>,
+    pub status: ImportStatus,
+    pub answers: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Written anyway, e.g. without an owner whose email is not registered here
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl ImportResult {
+    // Marks a record that passed validation as invalid after all, the first error is kept
+    pub fn reject(&mut self, error: String) {
+        if self.status != ImportStatus::Invalid {
+            self.status = ImportStatus::Invalid;
+            self.error = Some(error);
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportReport {
+    pub created: usize,
+    pub updated: usize,
+    pub invalid: usize,
+    pub records: Vec<ImportResult>,
+}
+
+impl ImportReport {
+    pub fn new(records: Vec<ImportResult>) -> Self {
+        let count = |status| records.iter().filter(|r| r.status == status).count();
+        ImportReport {
+            created: count(ImportStatus::Created),
+            updated: count(ImportStatus::Updated),
+            invalid: count(ImportStatus::Invalid),
+            records,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(json: &str) -> Result<QuestionRecord, String> {
+        serde_json::from_str::<QuestionRecord>(json)
+            .map_err(|e| e.to_string())
+            .and_then(QuestionRecord::validated)
+    }
+
+    #[test]
+    fn valid_record_has_its_tags_cleaned_up() {
+        let record = record(
+            r#"{"id": 3, "title": "Title", "content": "Some text", "tags": [" Rust", "rust", "Axum"],
+                "answers": [{"id": 4, "content": "An answer", "accepted": true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(record.id, Some(3));
+        assert_eq!(
+            record.tags,
+            Some(vec!["rust".to_string(), "axum".to_string()])
+        );
+        assert_eq!(record.answers[0].id, Some(4));
+        assert!(record.hidden.is_none() && record.flagged.is_none());
+    }
+
+    #[test]
+    fn ids_may_be_numbers_strings_or_missing() {
+        assert_eq!(
+            record(r#"{"id": "12", "title": "T", "content": "C"}"#)
+                .unwrap()
+                .id,
+            Some(12)
+        );
+        assert_eq!(
+            record(r#"{"id": null, "title": "T", "content": "C"}"#)
+                .unwrap()
+                .id,
+            None
+        );
+        assert_eq!(
+            record(r#"{"title": "T", "content": "C"}"#).unwrap().id,
+            None
+        );
+    }
+
+    #[test]
+    fn bad_ids_are_refused() {
+        for json in [
+            r#"{"id": "twelve", "title": "T", "content": "C"}"#,
+            r#"{"id": 1.5, "title": "T", "content": "C"}"#,
+            r#"{"id": 0, "title": "T", "content": "C"}"#,
+            r#"{"id": -4, "title": "T", "content": "C"}"#,
+            r#"{"title": "T", "content": "C", "answers": [{"id": "x", "content": "A"}]}"#,
+            r#"{"title": "T", "content": "C", "answers": [{"id": 0, "content": "A"}]}"#,
+        ] {
+            assert!(record(json).is_err(), "{} should be refused", json);
+        }
+    }
+
+    #[test]
+    fn empty_title_or_content_is_refused() {
+        for json in [
+            r#"{"title": "", "content": "C"}"#,
+            r#"{"title": "   ", "content": "C"}"#,
+            r#"{"title": "T", "content": ""}"#,
+            r#"{"title": "T", "content": "C", "answers": [{"content": " "}]}"#,
+            r#"{"content": "C"}"#,
+        ] {
+            assert!(record(json).is_err(), "{} should be refused", json);
+        }
+        let long_title = format!(r#"{{"title": "{}", "content": "C"}}"#, "t".repeat(256));
+        assert!(record(&long_title).is_err());
+    }
+
+    #[test]
+    fn accepted_answers_are_checked() {
+        let two = record(
+            r#"{"title": "T", "content": "C", "answers": [
+                {"content": "A", "accepted": true}, {"content": "B", "accepted": true}]}"#,
+        );
+        assert_eq!(two.unwrap_err(), "at most one answer can be accepted");
+
+        let hidden = record(
+            r#"{"title": "T", "content": "C", "answers": [
+                {"content": "A", "accepted": true, "hidden": true}]}"#,
+        );
+        assert_eq!(hidden.unwrap_err(), "a hidden answer cannot be accepted");
+    }
+
+    #[test]
+    fn account_id_is_read_but_never_written() {
+        let record = record(r#"{"title": "T", "content": "C", "account_id": 5}"#).unwrap();
+        assert_eq!(record.account_id, Some(5));
+        let json = serde_json::to_value(&record).unwrap();
+        assert!(json.get("account_id").is_none());
+    }
+}
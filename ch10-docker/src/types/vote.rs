@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::error::MyError;
+
+// Body of a vote request, 1 is an up vote and -1 a down vote
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Vote {
+    pub value: i16,
+}
+
+impl Vote {
+    pub fn validated(self) -> Result<i16, MyError> {
+        match self.value {
+            1 | -1 => Ok(self.value),
+            _ => Err(MyError::ValidationError(
+                "vote value must be 1 or -1".to_string(),
+            )),
+        }
+    }
+}
+
+// Score returned after voting
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct VoteSummary {
+    pub score: i64,
+}